@@ -0,0 +1,62 @@
+// Copyright 2016 The Servo Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `quickcheck::Arbitrary` implementations for the public types of this crate, enabled by the
+//! `arbitrary` feature.
+//!
+//! `Au` belongs to the `app_units` crate, so it can't implement `Arbitrary` here; the `Point` and
+//! `Size` implementations generate their lengths by wrapping arbitrary `i32`s instead.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use app_units::Au;
+use exclusions::{Point, Side, Size};
+use map::SplayMap;
+use quickcheck::{Arbitrary, Gen};
+
+impl<K, V> Arbitrary for SplayMap<K, V> where K: Arbitrary + Ord, V: Arbitrary {
+    fn arbitrary<G: Gen>(gen: &mut G) -> SplayMap<K, V> {
+        let pairs: Vec<(K, V)> = Arbitrary::arbitrary(gen);
+        pairs.into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = SplayMap<K, V>>> {
+        let pairs: Vec<(K, V)> = self.clone().into_iter().collect();
+        Box::new(pairs.shrink().map(|pairs| pairs.into_iter().collect()))
+    }
+}
+
+impl Arbitrary for Point {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Point {
+        Point {
+            inline: Au(Arbitrary::arbitrary(gen)),
+            block: Au(Arbitrary::arbitrary(gen)),
+        }
+    }
+}
+
+impl Arbitrary for Size {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Size {
+        Size {
+            inline: Au(Arbitrary::arbitrary(gen)),
+            block: Au(Arbitrary::arbitrary(gen)),
+        }
+    }
+}
+
+impl Arbitrary for Side {
+    fn arbitrary<G: Gen>(gen: &mut G) -> Side {
+        if Arbitrary::arbitrary(gen) {
+            Side::Left
+        } else {
+            Side::Right
+        }
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright 2016 The Servo Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Arithmetic on app units beyond what the `app_units` crate provides.
+//!
+//! `Au` is defined in `app_units`, so these methods live on the `AuExt` extension trait. Note that
+//! the `Add` and `Sub` implementations in `app_units` wrap around on overflow. `min`, `max`, and
+//! `clamp` come from the `Ord` implementation of `Au`. `app_units` implements `Rem<i32>` but not
+//! `Rem<Au>`, which can't be implemented here, so take the remainder by another length with
+//! `length % other.0` or `rem_euclid`.
+
+use app_units::Au;
+use core::borrow::Borrow;
+use core::fmt::{self, Display, Formatter};
+
+/// Extra arithmetic on `Au`.
+pub trait AuExt {
+    /// A length of zero.
+    const ZERO: Au;
+
+    /// The largest representable length.
+    const MAX: Au;
+
+    /// The smallest representable length.
+    const MIN: Au;
+
+    /// Adds two lengths, returning `None` if the result overflows.
+    fn checked_add(self, other: Au) -> Option<Au>;
+
+    /// Subtracts a length from this one, returning `None` if the result overflows.
+    fn checked_sub(self, other: Au) -> Option<Au>;
+
+    /// Negates this length, returning `None` if the result overflows, which only happens for
+    /// `Au(i32::MIN)`.
+    fn checked_neg(self) -> Option<Au>;
+
+    /// Adds two lengths, clamping the result to the range of `Au`.
+    fn saturating_add(self, other: Au) -> Au;
+
+    /// Subtracts a length from this one, clamping the result to the range of `Au`.
+    fn saturating_sub(self, other: Au) -> Au;
+
+    /// Negates this length, clamping the result to the range of `Au`, so that `Au(i32::MIN)`
+    /// becomes `Au(i32::MAX)`.
+    fn saturating_neg(self) -> Au;
+
+    /// Returns the absolute value of this length, returning `None` if the result overflows, which
+    /// only happens for `Au(i32::MIN)`.
+    fn checked_abs(self) -> Option<Au>;
+
+    /// Returns the absolute value of this length. `Au(i32::MIN)` has no positive counterpart, so
+    /// it saturates to `Au(i32::MAX)`.
+    fn abs(self) -> Au;
+
+    /// Returns the remainder of dividing this length by `other`, which is always nonnegative, as
+    /// with `i32::rem_euclid`. Panics if `other` is zero.
+    fn rem_euclid(self, other: Au) -> Au;
+
+    /// Returns true if this length is a whole multiple of `other`, which may be negative. Zero is
+    /// only a multiple of itself.
+    #[allow(clippy::wrong_self_convention)]
+    fn is_multiple_of(self, other: Au) -> bool;
+
+    /// Rounds this length to the nearest multiple of `grid`, which must be positive. Halfway cases
+    /// round toward positive infinity, and results out of range saturate.
+    fn round_to_nearest(self, grid: Au) -> Au;
+
+    /// Rounds this length down to a multiple of `grid`, which must be positive, toward negative
+    /// infinity. Results out of range saturate.
+    fn round_down_to(self, grid: Au) -> Au;
+
+    /// Returns an object that displays this length in both CSS pixels and app units, like
+    /// `12px (720au)`.
+    fn display(self) -> DisplayAu;
+}
+
+impl AuExt for Au {
+    const ZERO: Au = Au(0);
+    const MAX: Au = Au(i32::MAX);
+    const MIN: Au = Au(i32::MIN);
+
+    #[inline]
+    fn checked_add(self, other: Au) -> Option<Au> {
+        self.0.checked_add(other.0).map(Au)
+    }
+
+    #[inline]
+    fn checked_sub(self, other: Au) -> Option<Au> {
+        self.0.checked_sub(other.0).map(Au)
+    }
+
+    #[inline]
+    fn checked_neg(self) -> Option<Au> {
+        self.0.checked_neg().map(Au)
+    }
+
+    #[inline]
+    fn saturating_add(self, other: Au) -> Au {
+        Au(self.0.saturating_add(other.0))
+    }
+
+    #[inline]
+    fn saturating_sub(self, other: Au) -> Au {
+        Au(self.0.saturating_sub(other.0))
+    }
+
+    #[inline]
+    fn saturating_neg(self) -> Au {
+        Au(self.0.saturating_neg())
+    }
+
+    #[inline]
+    fn checked_abs(self) -> Option<Au> {
+        self.0.checked_abs().map(Au)
+    }
+
+    #[inline]
+    fn abs(self) -> Au {
+        Au(self.0.saturating_abs())
+    }
+
+    #[inline]
+    fn rem_euclid(self, other: Au) -> Au {
+        Au(self.0.rem_euclid(other.0))
+    }
+
+    #[inline]
+    fn is_multiple_of(self, other: Au) -> bool {
+        if other.0 == 0 {
+            return self.0 == 0
+        }
+        // Widened so that `i32::MIN % -1` doesn't overflow.
+        (self.0 as i64) % (other.0 as i64) == 0
+    }
+
+    #[inline]
+    fn round_to_nearest(self, grid: Au) -> Au {
+        assert!(grid.0 > 0, "AuExt::round_to_nearest(): Grid must be positive!");
+        let grid = grid.0 as i64;
+        saturate((self.0 as i64 + grid / 2).div_euclid(grid) * grid)
+    }
+
+    #[inline]
+    fn round_down_to(self, grid: Au) -> Au {
+        assert!(grid.0 > 0, "AuExt::round_down_to(): Grid must be positive!");
+        let grid = grid.0 as i64;
+        saturate((self.0 as i64).div_euclid(grid) * grid)
+    }
+
+    #[inline]
+    fn display(self) -> DisplayAu {
+        DisplayAu(self)
+    }
+}
+
+/// Clamps a widened length to the range of `Au`.
+fn saturate(length: i64) -> Au {
+    Au(length.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+}
+
+/// Displays an `Au` in CSS pixels and app units. Created by `AuExt::display`.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayAu(Au);
+
+impl Display for DisplayAu {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(formatter, "{}px ({}au)", self.0.to_f64_px(), (self.0).0)
+    }
+}
+
+/// Adds up lengths, saturating at the bounds of `Au` so that summing band lengths that reach the
+/// `Au::MAX` sentinel doesn't wrap around.
+///
+/// This stands in for `Sum`, which can't be implemented for `Au` outside of `app_units`. It
+/// accepts both `Au` and `&Au` items.
+pub fn sum<I>(lengths: I) -> Au where I: IntoIterator, I::Item: Borrow<Au> {
+    lengths.into_iter().fold(Au::ZERO, |sum, length| sum.saturating_add(*length.borrow()))
+}
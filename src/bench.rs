@@ -1,9 +1,11 @@
 // Any copyright is dedicated to the Public Domain.
 // http://creativecommons.org/publicdomain/zero/1.0/
 
+use map::SplayMap;
 use quickcheck::{Arbitrary, StdGen};
-use rand;
+use rand::{self, Rng};
 use rust_test::Bencher;
+use std::vec::Vec;
 use test::{self, Exclusion, InlineSize};
 
 fn generate_test_cases(count: usize) -> Vec<(InlineSize, Vec<Exclusion>)> {
@@ -36,3 +38,70 @@ fn bench_128(bencher: &mut Bencher) { bench(128, bencher) }
 fn bench_1024(bencher: &mut Bencher) { bench(1024, bencher) }
 
 
+
+fn generate_map(count: usize) -> SplayMap<u32, u32> {
+    let mut rng = rand::thread_rng();
+    (0..count).map(|_| (rng.gen(), rng.gen())).collect()
+}
+
+#[bench]
+fn bench_clone_1024(bencher: &mut Bencher) {
+    let (source, mut dest) = (generate_map(1024), generate_map(1024));
+    bencher.iter(|| dest = source.clone());
+}
+#[bench]
+fn bench_clone_from_1024(bencher: &mut Bencher) {
+    let (source, mut dest) = (generate_map(1024), generate_map(1024));
+    bencher.iter(|| dest.clone_from(&source));
+}
+
+// Snapshots a map after inserting and removing a key, as in speculative layout, so the source and
+// the previous snapshot mostly overlap.
+#[bench]
+fn bench_clone_overlapping_1024(bencher: &mut Bencher) {
+    let (mut source, mut rng) = (generate_map(1024), rand::thread_rng());
+    let mut dest = source.clone();
+    bencher.iter(|| {
+        let key = rng.gen();
+        source.insert(key, 0);
+        source.remove(&key);
+        dest = source.clone()
+    });
+}
+#[bench]
+fn bench_clone_from_overlapping_1024(bencher: &mut Bencher) {
+    let (mut source, mut rng) = (generate_map(1024), rand::thread_rng());
+    let mut dest = source.clone();
+    bencher.iter(|| {
+        let key = rng.gen();
+        source.insert(key, 0);
+        source.remove(&key);
+        dest.clone_from(&source)
+    });
+}
+
+fn generate_pairs(count: usize) -> Vec<(u32, u32)> {
+    let mut rng = rand::thread_rng();
+    (0..count).map(|_| (rng.gen(), rng.gen())).collect()
+}
+
+// `Bencher` can't leave setup out of the timing, so both of these clone the map and the batch of
+// pairs on each iteration. Copying the batch takes a tiny fraction of the time inserting it does.
+#[bench]
+fn bench_extend_100k(bencher: &mut Bencher) {
+    let (map, pairs) = (generate_map(1024), generate_pairs(100_000));
+    bencher.iter(|| {
+        let mut map = map.clone();
+        map.extend(pairs.clone());
+        map
+    });
+}
+#[bench]
+fn bench_bulk_insert_100k(bencher: &mut Bencher) {
+    let (map, pairs) = (generate_map(1024), generate_pairs(100_000));
+    bencher.iter(|| {
+        let mut map = map.clone();
+        map.bulk_insert(pairs.clone());
+        map
+    });
+}
@@ -11,40 +11,81 @@
 //! The algorithm that manages exclusions and places objects according to the rules in CSS 2.1 §
 //! 9.5.1.
 
+use alloc::vec::Vec;
 use app_units::Au;
+use au::AuExt;
 use map::SplayMap;
-use std::cmp::Ordering;
-use std::fmt::{self, Debug, Formatter};
-use std::i32;
-use std::iter;
-
-const MAX_AU: Au = Au(i32::MAX);
+use core::cmp::{self, Ordering};
+use core::fmt::{self, Debug, Display, Formatter};
+use core::iter;
+use core::ops::{Add, Sub};
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::string::String;
 
 /// Tracks exclusions and allows objects to be placed adjacent to them.
 #[derive(Clone)]
 pub struct Exclusions {
     bands: SplayMap<Au, Band>,
     inline_size: Au,
+    direction: Direction,
+    has_exclusions: bool,
+    /// Every exclusion, so that `remove` can work out what the bands it touched fall back to. A
+    /// band only records the largest exclusion that overlaps it, so this holds one entry per
+    /// `exclude` call (or per slice of a circle) until it is removed or the zone is cleared.
+    excluded: Vec<(Side, Size)>,
+    /// The block position where the first band starts, such as below the top padding of the
+    /// containing block.
+    block_start: Au,
+    /// The grid given to `snap_to_grid`, if any, which the sizes of the exclusions are rounded to
+    /// before they are applied to the bands.
+    grid: Option<Au>,
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Band {
+/// A horizontal strip of the zone in which the exclusions intrude by the same amount.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Band {
     left: Au,
     right: Au,
     length: Au,
 }
 
 impl Band {
-    fn new(left: Au, right: Au, length: Au) -> Band {
+    /// Creates a band with the given negated left and right insets and block size, as returned
+    /// by `left`, `right`, and `length`.
+    pub fn new(left: Au, right: Au, length: Au) -> Band {
         Band {
-            left: left,
-            right: right,
-            length: length,
+            left,
+            right,
+            length,
         }
     }
 
-    fn available_size(&self, inline_size: Au) -> Au {
-        inline_size + self.left + self.right
+    /// Returns the inline size that exclusions take up on the left side of this band, negated.
+    pub fn left(&self) -> Au {
+        self.left
+    }
+
+    /// Returns the inline size that exclusions take up on the right side of this band, negated.
+    pub fn right(&self) -> Au {
+        self.right
+    }
+
+    /// Returns the size of this band in the block direction.
+    pub fn length(&self) -> Au {
+        self.length
+    }
+
+    /// Returns the inline size left over in this band in a zone of the given inline size.
+    pub fn available_size(&self, inline_size: Au) -> Au {
+        inline_size.saturating_add(self.left).saturating_add(self.right)
+    }
+
+    /// Returns true if this band has the same insets as the other one, regardless of their
+    /// lengths, so that the two could be merged if they were adjacent.
+    pub fn can_merge_with(&self, other: &Band) -> bool {
+        (self.left, self.right) == (other.left, other.right)
     }
 
     fn get(&self, side: Side) -> Au {
@@ -75,8 +116,8 @@ impl Point {
     /// Creates a new logical point.
     pub fn new(inline: Au, block: Au) -> Point {
         Point {
-            inline: inline,
-            block: block,
+            inline,
+            block,
         }
     }
 }
@@ -90,12 +131,114 @@ pub struct Size {
     pub block: Au,
 }
 
+impl Add<Size> for Point {
+    type Output = Point;
+    fn add(self, size: Size) -> Point {
+        Point::new(self.inline + size.inline, self.block + size.block)
+    }
+}
+
+impl Sub<Size> for Point {
+    type Output = Point;
+    fn sub(self, size: Size) -> Point {
+        Point::new(self.inline - size.inline, self.block - size.block)
+    }
+}
+
+impl Sub<Point> for Point {
+    type Output = Size;
+    fn sub(self, other: Point) -> Size {
+        Size::new(self.inline - other.inline, self.block - other.block)
+    }
+}
+
 impl Size {
     /// Creates a new logical size.
     pub fn new(inline: Au, block: Au) -> Size {
         Size {
-            inline: inline,
-            block: block,
+            inline,
+            block,
+        }
+    }
+
+    /// Returns the area covered by this size, in square app units. Like multiplication of `Au`,
+    /// this wraps around on overflow.
+    pub fn area(self) -> Au {
+        self.inline * self.block.0
+    }
+}
+
+impl Add<Size> for Size {
+    type Output = Size;
+    fn add(self, other: Size) -> Size {
+        Size::new(self.inline + other.inline, self.block + other.block)
+    }
+}
+
+/// A logical rectangle.
+#[derive(Clone, Copy, Debug)]
+pub struct Rectangle {
+    /// The top left corner of the rectangle.
+    pub origin: Point,
+    /// The size of the rectangle.
+    pub size: Size,
+}
+
+impl Rectangle {
+    /// Creates a new logical rectangle.
+    pub fn new(origin: Point, size: Size) -> Rectangle {
+        Rectangle {
+            origin,
+            size,
+        }
+    }
+
+    /// Returns the inline position of the end edge of the rectangle (normally the right).
+    pub fn inline_end(&self) -> Au {
+        self.origin.inline + self.size.inline
+    }
+
+    /// Returns the block position of the end edge of the rectangle (normally the bottom).
+    pub fn block_end(&self) -> Au {
+        self.origin.block + self.size.block
+    }
+
+    /// Returns true if this rectangle and the other one overlap. Rectangles that only share an
+    /// edge don't overlap.
+    pub fn intersects(&self, other: &Rectangle) -> bool {
+        self.origin.inline < other.inline_end() && other.origin.inline < self.inline_end() &&
+            self.origin.block < other.block_end() && other.origin.block < self.block_end()
+    }
+
+    /// Returns true if the point lies within this rectangle. The start edges are inside the
+    /// rectangle, and the end edges are outside it.
+    pub fn contains(&self, point: Point) -> bool {
+        point.inline >= self.origin.inline && point.inline < self.inline_end() &&
+            point.block >= self.origin.block && point.block < self.block_end()
+    }
+}
+
+/// The margins around an object, in logical directions.
+#[derive(Clone, Copy, Debug)]
+pub struct Margins {
+    /// The margin on the side where inline content starts (normally the left).
+    pub inline_start: Au,
+    /// The margin on the side where inline content ends (normally the right).
+    pub inline_end: Au,
+    /// The margin on the side where block content starts (normally the top).
+    pub block_start: Au,
+    /// The margin on the side where block content ends (normally the bottom).
+    pub block_end: Au,
+}
+
+impl Margins {
+    /// Creates a new set of logical margins.
+    pub fn new(inline_start: Au, inline_end: Au, block_start: Au, block_end: Au) -> Margins {
+        Margins {
+            inline_start,
+            inline_end,
+            block_start,
+            block_end,
         }
     }
 }
@@ -113,11 +256,103 @@ impl Placement {
     fn new(origin: &Point, available_inline_size: Au) -> Placement {
         Placement {
             origin: *origin,
-            available_inline_size: available_inline_size,
+            available_inline_size,
         }
     }
 }
 
+/// An iterator over the bands of an `Exclusions` and their block positions, in block order.
+pub struct Bands<'a> {
+    exclusions: &'a Exclusions,
+    next_block_position: Option<Au>,
+}
+
+impl<'a> Iterator for Bands<'a> {
+    type Item = (Au, Band);
+    fn next(&mut self) -> Option<(Au, Band)> {
+        let (block_position, band) = self.exclusions.band_at(self.next_block_position?);
+        let block_end = block_position + band.length;
+        self.next_block_position = if block_end == Au::MAX { None } else { Some(block_end) };
+        Some((block_position, *band))
+    }
+}
+
+/// Why `Exclusions::try_place` couldn't place an object.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlacementError {
+    /// The object is wider than the space available below all the exclusions.
+    TooWide {
+        /// The inline size of the object.
+        requested: Au,
+        /// The inline size available in the widest band.
+        available: Au,
+    },
+    /// No band could hold the object, which only happens if the bands are malformed.
+    NoBand,
+}
+
+impl Display for PlacementError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PlacementError::TooWide { requested, available } => {
+                write!(formatter,
+                       "object {:?} wide exceeds the {:?} available",
+                       requested,
+                       available)
+            }
+            PlacementError::NoBand => formatter.write_str("no band can hold the object"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for PlacementError {}
+
+/// Why `Exclusions::from_bands` rejected a set of bands. Each variant holds the block position of
+/// the offending band where there is one.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BandsError {
+    /// There were no bands.
+    Empty,
+    /// A band didn't start where the band above it ended, or the first band didn't start at the
+    /// block position where the content of the zone starts.
+    NotContiguous(Au),
+    /// A band had a block size of zero or less.
+    NotPositive(Au),
+    /// A band had an inset that was negative, or larger than the inset on the same side in the
+    /// band above it, so no set of exclusions could have produced it.
+    NotStaircase(Au),
+    /// The last band didn't reach the bottom of the zone at `Au::MAX`.
+    Unterminated,
+}
+
+impl Display for BandsError {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BandsError::Empty => formatter.write_str("there are no bands"),
+            BandsError::NotContiguous(block_position) => {
+                write!(formatter,
+                       "the band at {:?} doesn't start where the one above it ends",
+                       block_position)
+            }
+            BandsError::NotPositive(block_position) => {
+                write!(formatter, "the band at {:?} isn't tall enough", block_position)
+            }
+            BandsError::NotStaircase(block_position) => {
+                write!(formatter,
+                       "the insets of the band at {:?} can't come from any exclusions",
+                       block_position)
+            }
+            BandsError::Unterminated => {
+                formatter.write_str("the last band doesn't reach the bottom of the zone")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for BandsError {}
+
 /// Left or right.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Side {
@@ -125,11 +360,45 @@ pub enum Side {
     Right,
 }
 
+impl Side {
+    /// Returns both sides, for iterating over them.
+    pub fn all() -> [Side; 2] {
+        [Side::Left, Side::Right]
+    }
+
+    /// Returns the other side.
+    pub fn opposite(self) -> Side {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+}
+
+/// The direction in which inline content flows.
+///
+/// In a right-to-left zone, objects placed or excluded on the `Left` side go on the right side of
+/// the zone and vice versa, as do the sides of `ClearSide`. Inline positions and band insets are
+/// still measured from the physical left and right edges.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Ltr,
+    Rtl,
+}
+
+/// Which sides an object clears exclusions on, as in the CSS `clear` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClearSide {
+    Left,
+    Right,
+    Both,
+}
+
 impl Debug for Exclusions {
     fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
-        try!(writeln!(formatter, "Exclusions(inline_size={:?}): bands:", self.inline_size));
-        for (block_position, band) in self.bands.clone().into_iter() {
-            try!(writeln!(formatter, "    {:?} {:?}", block_position, band));
+        writeln!(formatter, "Exclusions(inline_size={:?}): bands:", self.inline_size)?;
+        for (block_position, band) in self.bands.iter() {
+            writeln!(formatter, "    {:?} {:?}", block_position, band)?;
         }
         Ok(())
     }
@@ -139,43 +408,432 @@ impl Exclusions {
     /// Creates a new rectangular exclusion zone infinitely long in the block direction with the
     /// given inline size.
     ///
-    /// The zone starts out with no exclusions in it.
+    /// The zone starts out with no exclusions in it, and its inline direction is left-to-right.
     pub fn new(inline_size: Au) -> Exclusions {
+        Exclusions::with_direction(inline_size, Direction::Ltr)
+    }
+
+    /// Creates a new exclusion zone like `new`, but with the given inline direction.
+    pub fn with_direction(inline_size: Au, direction: Direction) -> Exclusions {
         Exclusions {
-            bands: iter::once((Au(0), Band::new(Au(0), Au(0), MAX_AU))).collect(),
-            inline_size: inline_size,
+            bands: iter::once((Au::ZERO, Band::new(Au::ZERO, Au::ZERO, Au::MAX))).collect(),
+            inline_size,
+            direction,
+            has_exclusions: false,
+            excluded: vec![],
+            block_start: Au::ZERO,
+            grid: None,
         }
     }
 
+    /// Creates a new left-to-right exclusion zone like `new`, but whose content starts at the given
+    /// block position, such as below the top border and padding of the containing block.
+    ///
+    /// Block positions are still measured from the top of the zone, but nothing is placed above
+    /// `block_start`, and exclusions that end at or above it are ignored.
+    pub fn with_origin(inline_size: Au, block_start: Au) -> Exclusions {
+        assert!(block_start >= Au::ZERO && block_start < Au::MAX,
+                "Exclusions::with_origin(): Block start is out of range!");
+        let mut exclusions = Exclusions::new(inline_size);
+        exclusions.block_start = block_start;
+        exclusions.reset_bands();
+        exclusions
+    }
+
+    /// Creates an exclusion zone with the given inline size, direction, and block position where
+    /// its content starts, as given to `with_direction` and `with_origin`, from its bands and their
+    /// block positions in block order, as returned by `bands`, without replaying any exclusions.
+    ///
+    /// The bands must cover the zone from `block_start` to `Au::MAX` without gaps, and their insets
+    /// must not grow going down the zone. Each band's insets are treated as exclusions reaching
+    /// the bottom of that band, which is what `remove` will retract.
+    pub fn from_bands<I>(inline_size: Au, direction: Direction, block_start: Au, bands: I)
+                         -> Result<Exclusions, BandsError>
+                         where I: IntoIterator<Item = (Au, Band)> {
+        assert!(block_start >= Au::ZERO && block_start < Au::MAX,
+                "Exclusions::from_bands(): Block start is out of range!");
+        let mut exclusions = Exclusions::with_direction(inline_size, direction);
+        exclusions.block_start = block_start;
+        let bands: Vec<(Au, Band)> = bands.into_iter().collect();
+        let mut next_block_position = block_start;
+        let mut previous_band = Band::new(Au::MIN, Au::MIN, Au::ZERO);
+        for &(block_position, band) in &bands {
+            if block_position != next_block_position {
+                return Err(BandsError::NotContiguous(block_position))
+            }
+            if band.length <= Au::ZERO {
+                return Err(BandsError::NotPositive(block_position))
+            }
+            if band.left > Au::ZERO || band.right > Au::ZERO || band.left < previous_band.left ||
+                    band.right < previous_band.right {
+                return Err(BandsError::NotStaircase(block_position))
+            }
+            next_block_position = block_position.checked_add(band.length)
+                                                .ok_or(BandsError::Unterminated)?;
+            for &side in &Side::all() {
+                if band.get(side) != Au::ZERO {
+                    // The physical side of a logical side is also the logical side of a physical
+                    // one.
+                    let size = Size::new(-band.get(side), next_block_position);
+                    exclusions.excluded.push((exclusions.physical_side(side), size));
+                }
+            }
+            previous_band = band;
+        }
+        if bands.is_empty() {
+            return Err(BandsError::Empty)
+        }
+        if next_block_position != Au::MAX {
+            return Err(BandsError::Unterminated)
+        }
+
+        exclusions.has_exclusions = !exclusions.excluded.is_empty();
+        exclusions.bands = SplayMap::from_sorted_iter(bands);
+        Ok(exclusions)
+    }
+
+    /// Removes all exclusions, leaving the zone as it was when it was created.
+    pub fn clear(&mut self) {
+        self.excluded.clear();
+        self.reset_bands();
+    }
+
+    /// Resets the bands to the single band of an empty zone, without forgetting the exclusions.
+    fn reset_bands(&mut self) {
+        self.bands.clear();
+        let length = Au::MAX - self.block_start;
+        self.bands.insert(self.block_start, Band::new(Au::ZERO, Au::ZERO, length));
+        self.has_exclusions = false;
+    }
+
+    /// Returns the number of bands that the zone is divided into.
+    pub fn band_count(&self) -> usize {
+        self.bands.len()
+    }
+
+    /// Returns true if any exclusions intrude into the zone.
+    ///
+    /// This is tracked as exclusions are added, so it takes O(1) time.
+    pub fn has_exclusions(&self) -> bool {
+        self.has_exclusions
+    }
+
+    /// Changes the inline size of the zone, keeping its exclusions.
+    ///
+    /// Each band stores its insets relative to the edge that they intrude from, so they stay
+    /// valid: left exclusions stay put, while right exclusions move with the right edge of the
+    /// zone. If the zone shrinks below the combined size of the exclusions in a band, that band
+    /// ends up with a negative available inline size.
+    pub fn resize(&mut self, inline_size: Au) {
+        self.inline_size = inline_size;
+    }
+
     /// Places an object so that it does not overlap any exclusions according to the CSS float
     /// placement rules.
     ///
     /// The object is aligned either to the left or right, depending on the size.
     pub fn place(&mut self, alignment: Side, size: &Size) -> Placement {
-        let block_position =
+        self.find_placement(alignment, size).expect("Exclusions::place(): Didn't find a band!")
+    }
+
+    /// Places an object like `place`, returning the rectangle it occupies.
+    pub fn place_rect(&mut self, alignment: Side, size: &Size) -> Rectangle {
+        Rectangle::new(self.place(alignment, size).origin, *size)
+    }
+
+    /// Like `place`, but returns an error instead of placing an object too wide to fit anywhere in
+    /// the zone below all the exclusions.
+    pub fn try_place(&self, alignment: Side, size: &Size) -> Result<Point, PlacementError> {
+        let placement = self.find_placement(alignment, size).ok_or(PlacementError::NoBand)?;
+        if placement.available_inline_size >= size.inline {
+            Ok(placement.origin)
+        } else {
+            let available = self.bands.iter().map(|(_, band)| {
+                cmp::max(band.available_size(self.inline_size), Au::ZERO)
+            }).max().unwrap_or(Au::ZERO);
+            Err(PlacementError::TooWide {
+                requested: size.inline,
+                available,
+            })
+        }
+    }
+
+    /// Finds where an object that may go on either side would be placed on each side, like
+    /// `place`, and returns whichever side puts it higher up along with its origin there. Ties
+    /// go to `Left`.
+    pub fn place_best(&self, size: &Size) -> (Side, Point) {
+        let find = |side| {
+            self.find_placement(side, size)
+                .expect("Exclusions::place_best(): Didn't find a band!")
+                .origin
+        };
+        let (left, right) = (find(Side::Left), find(Side::Right));
+        if right.block < left.block {
+            (Side::Right, right)
+        } else {
+            (Side::Left, left)
+        }
+    }
+
+    /// Places an object surrounded by the given margins, like `place`.
+    ///
+    /// Space is found for the object's margin box, which is `size` plus the margins. The origin
+    /// of the returned placement is that of the border box, which is the margin box inset by the
+    /// left and block-start margins. The available inline size is that of the margin box.
+    pub fn place_with_margins(&mut self, alignment: Side, size: &Size, margins: &Margins)
+                              -> Placement {
+        let margin_box_size = *size + Size::new(margins.inline_start + margins.inline_end,
+                                                margins.block_start + margins.block_end);
+        let placement = self.place(alignment, &margin_box_size);
+        let (left_margin, _) = self.physical_margins(margins);
+        let origin = placement.origin + Size::new(left_margin, margins.block_start);
+        Placement::new(&origin, placement.available_inline_size)
+    }
+
+    fn find_placement(&self, alignment: Side, size: &Size) -> Option<Placement> {
+        let mut block_position =
             self.bands
                 .lower_bound_with(|&band_block_start, band| {
                     compare_inline_size(band_block_start, band, size, self.inline_size)
-                }).expect("Exclusions::place(): Didn't find a band!").0;
-        let band = self.bands.get(&block_position).unwrap();
-        let inline_position = match alignment {
-            Side::Left => -band.left,
-            Side::Right => self.inline_size + band.right - size.inline,
+                })?.0;
+
+        // An object taller than the band it starts in must also fit within every band below that
+        // it overlaps, so move it below any of those bands that are too narrow.
+        loop {
+            let mut narrow_band_end = None;
+            self.each_band_in(block_position, size.block, |band_block_start, band| {
+                let band_block_end = band_block_start + band.length;
+                if band.available_size(self.inline_size) < size.inline &&
+                        band_block_end != Au::MAX {
+                    narrow_band_end = Some(band_block_end);
+                    return false
+                }
+                true
+            });
+            match narrow_band_end {
+                Some(band_block_end) => block_position = band_block_end,
+                None => break,
+            }
+        }
+
+        let (left_edge, right_edge) = self.line_box(block_position, size.block);
+        let inline_position = match self.physical_side(alignment) {
+            Side::Left => left_edge,
+            Side::Right => right_edge - size.inline,
         };
         let origin = Point::new(inline_position, block_position);
-        Placement::new(&origin, band.available_size(self.inline_size))
+        Some(Placement::new(&origin, right_edge - left_edge))
+    }
+
+    /// Returns the inline size left over for content at the given block position once the
+    /// exclusions there are subtracted.
+    pub fn available_inline_size(&self, block_position: Au) -> Au {
+        self.band_at(block_position).1.available_size(self.inline_size)
+    }
+
+    /// Returns the inline position of the left edge of the area clear of exclusions at the given
+    /// block position.
+    pub fn leftmost_available(&self, block_position: Au) -> Au {
+        -self.band_at(block_position).1.left
+    }
+
+    /// Returns the inline position of the right edge of the area clear of exclusions at the given
+    /// block position.
+    pub fn rightmost_available(&self, block_position: Au) -> Au {
+        self.inline_size + self.band_at(block_position).1.right
+    }
+
+    /// Returns the inline range `(left_edge, right_edge)` that is clear of exclusions across every
+    /// band overlapping the block range `[block_position, block_position + block_size)`, such as
+    /// the range occupied by a line of text.
+    ///
+    /// A zero block size uses the band at `block_position`.
+    pub fn line_box(&self, block_position: Au, block_size: Au) -> (Au, Au) {
+        let (mut left_edge, mut right_edge) = (Au::MIN, Au::MAX);
+        self.each_band_in(block_position, block_size, |_, band| {
+            left_edge = cmp::max(left_edge, -band.left);
+            right_edge = cmp::min(right_edge, self.inline_size + band.right);
+            true
+        });
+        (left_edge, right_edge)
+    }
+
+    /// Returns the inline size of the widest object that could be placed across the block range
+    /// `[block_position, block_position + block_size)` without overlapping any exclusions. This
+    /// is the width of the `line_box` for that range, or zero if the exclusions on either side
+    /// overlap.
+    pub fn max_inline_run(&self, block_position: Au, block_size: Au) -> Au {
+        let (left_edge, right_edge) = self.line_box(block_position, block_size);
+        cmp::max(right_edge - left_edge, Au::ZERO)
+    }
+
+    /// Returns true if an object with the given origin and size would lie within the available
+    /// inline range of every band it overlaps, such as when checking a box positioned by hand.
+    pub fn fits(&self, origin: Point, size: &Size) -> bool {
+        let (left_edge, right_edge) = self.line_box(origin.block, size.block);
+        origin.inline >= left_edge && origin.inline + size.inline <= right_edge
+    }
+
+    /// Returns the first block position at or below `block_position` where no exclusions intrude
+    /// on the given side or sides. This is where an object with the CSS `clear` property goes.
+    pub fn clearance(&self, block_position: Au, clear: ClearSide) -> Au {
+        let clear = match (self.direction, clear) {
+            (Direction::Rtl, ClearSide::Left) => ClearSide::Right,
+            (Direction::Rtl, ClearSide::Right) => ClearSide::Left,
+            (_, clear) => clear,
+        };
+        let mut clearance = block_position;
+        self.each_band_in(block_position, Au::MAX - block_position, |band_block_position, band| {
+            clearance = cmp::max(band_block_position, block_position);
+            match clear {
+                ClearSide::Left => band.left != Au::ZERO,
+                ClearSide::Right => band.right != Au::ZERO,
+                ClearSide::Both => band.left != Au::ZERO || band.right != Au::ZERO,
+            }
+        });
+        clearance
     }
 
     /// Excludes a rectangular area of the given size, preventing any objects from being placed
     /// within it.
     ///
-    /// The excluded area touches the top left or top right of the zone, depending on the side.
+    /// The excluded area touches the top left or top right of the zone, depending on the side,
+    /// and ends at `size.block`; the band below it gets back whatever inline size the area took
+    /// up. An object placed partway down the zone is excluded by giving the distance from the top
+    /// of the zone to its bottom as the block size, as `exclude_with_margins` does. Exclusions that
+    /// end at or above the block position where the content starts, as given to `with_origin`,
+    /// are ignored.
     pub fn exclude(&mut self, side: Side, size: &Size) {
-        if size.inline == Au(0) || size.block == Au(0) {
+        if size.inline == Au::ZERO || size.block <= self.block_start {
             return
         }
 
-        self.split(size.block);
+        self.excluded.push((side, *size));
+        if let Some(size) = self.banded_size(size) {
+            self.add_exclusion(side, &size);
+        }
+    }
+
+    /// Returns the size that the given exclusion takes up in the bands, which is rounded to the
+    /// grid given to `snap_to_grid` if there is one, or `None` if it doesn't reach the bands.
+    fn banded_size(&self, size: &Size) -> Option<Size> {
+        let size = match self.grid {
+            Some(grid) => {
+                Size::new(snap_to_grid(size.inline, grid), snap_to_grid(size.block, grid))
+            }
+            None => *size,
+        };
+        if size.inline == Au::ZERO || size.block <= self.block_start {
+            None
+        } else {
+            Some(size)
+        }
+    }
+
+    /// Retracts an exclusion of the object occupying the rectangle with the given origin and size,
+    /// undoing `exclude_with_margins` with the same arguments and no margins, and returns false if
+    /// there is no such exclusion. An exclusion added with `exclude` is retracted by giving the
+    /// rectangle it excluded, which starts at the top of the zone and at its edge on that side.
+    ///
+    /// Only the bands whose inset on the given side came from this exclusion are touched: they
+    /// fall back to the inset of the band below it, then the remaining exclusions on that side
+    /// that end among them are applied again, and bands that become identical are merged.
+    pub fn remove(&mut self, side: Side, origin: Point, size: &Size) -> bool {
+        let size = self.excluded_size(side, &origin, size);
+        self.remove_excluded(side, &size)
+    }
+
+    /// Retracts an exclusion added by `exclude` with the given side and size, returning false if
+    /// there is no such exclusion.
+    fn remove_excluded(&mut self, side: Side, size: &Size) -> bool {
+        let index = match self.excluded.iter().rposition(|&(excluded_side, excluded_size)| {
+            excluded_side == side && excluded_size.inline == size.inline &&
+                excluded_size.block == size.block
+        }) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.excluded.swap_remove(index);
+        self.retract_exclusion(side, size);
+        true
+    }
+
+    /// Restores the bands that the given exclusion, which must no longer be in `excluded`, set the
+    /// inset of.
+    fn retract_exclusion(&mut self, side: Side, size: &Size) {
+        let size = match self.banded_size(size) {
+            Some(size) => size,
+            None => return,
+        };
+        let physical_side = self.physical_side(side);
+        let fallback = if size.block == Au::MAX {
+            Au::ZERO
+        } else {
+            self.band_at(size.block).1.get(physical_side)
+        };
+
+        // The insets only grow going up the zone, so the bands this exclusion set are the ones
+        // directly above its bottom edge that still have its inset.
+        let mut block_position = size.block;
+        while block_position > self.block_start {
+            let (band_block_position, band) = self.band_at(block_position - Au(1));
+            if band.get(physical_side) != -size.inline {
+                break
+            }
+            self.bands
+                .get_mut(&band_block_position)
+                .expect("Exclusions::remove(): Didn't find band to restore!")
+                .set(physical_side, fallback);
+            self.merge_with_band_above(block_position);
+            block_position = band_block_position;
+        }
+
+        for index in 0..self.excluded.len() {
+            let (excluded_side, excluded_size) = self.excluded[index];
+            let excluded_size = match self.banded_size(&excluded_size) {
+                Some(excluded_size) if excluded_side == side => excluded_size,
+                Some(_) | None => continue,
+            };
+            if excluded_size.block > block_position && -excluded_size.inline < fallback {
+                self.add_exclusion(excluded_side, &excluded_size);
+            }
+        }
+        let top_band = self.band_at(self.block_start).1;
+        self.has_exclusions = top_band.left != Au::ZERO || top_band.right != Au::ZERO;
+    }
+
+    /// Rounds the block positions, lengths, and insets of every band to the nearest multiple of
+    /// `grid`, such as `Au(60)` for whole CSS pixels, including the block position where the
+    /// content starts. Bands that end up with the same boundaries or insets are merged.
+    ///
+    /// The exclusions themselves are kept as they were given, so `remove` still takes the
+    /// unrounded sizes, and calling this again with another grid rounds them afresh. Exclusions
+    /// added or removed later are rounded to the grid too.
+    pub fn snap_to_grid(&mut self, grid: Au) {
+        assert!(grid > Au::ZERO, "Exclusions::snap_to_grid(): Grid must be positive!");
+        self.grid = Some(grid);
+        self.block_start = snap_to_grid(self.block_start, grid);
+        self.rebuild_bands();
+    }
+
+    /// Rebuilds the bands from scratch out of the exclusions in `excluded`.
+    fn rebuild_bands(&mut self) {
+        self.reset_bands();
+        for index in 0..self.excluded.len() {
+            let (side, size) = self.excluded[index];
+            if let Some(size) = self.banded_size(&size) {
+                self.add_exclusion(side, &size);
+            }
+        }
+    }
+
+    fn add_exclusion(&mut self, side: Side, size: &Size) {
+        let side = self.physical_side(side);
+
+        // The last band already ends at the largest possible block position.
+        if size.block != Au::MAX {
+            self.split(size.block);
+        }
 
         let (mut last_block_position, mut last_band): (Au, Option<Band>) = (size.block, None);
         loop {
@@ -201,6 +859,7 @@ impl Exclusions {
                     //  ├───┘
                     //  │
                     band.set(side, -size.inline);
+                    self.has_exclusions = true;
 
                     // Merge with the next band if we can.
                     //
@@ -215,8 +874,8 @@ impl Exclusions {
                     //  ├───┘
                     //  │
                     if let Some(ref last_band) = last_band {
-                        if band.left == last_band.left && band.right == last_band.right {
-                            band.length = band.length + last_band.length;
+                        if band.can_merge_with(last_band) {
+                            band.length += last_band.length;
                             band_to_delete = Some(last_block_position)
                         }
                     }
@@ -243,6 +902,210 @@ impl Exclusions {
                 self.bands.remove(&band_to_delete);
             }
         }
+
+        // The split above may have left two identical bands behind if the upper half didn't need
+        // extending.
+        self.merge_with_band_above(size.block);
+    }
+
+    /// Returns the lowest block position that any exclusion reaches, or zero if there are no
+    /// exclusions. Exclusions that extend all the way to the bottom of the zone are ignored.
+    pub fn content_bottom(&self) -> Au {
+        self.bands().filter_map(|(block_position, band)| {
+            let block_end = block_position + band.length;
+            if block_end != Au::MAX && (band.left != Au::ZERO || band.right != Au::ZERO) {
+                Some(block_end)
+            } else {
+                None
+            }
+        }).max().unwrap_or(Au::ZERO)
+    }
+
+    /// Draws the zone as a grid of `rows` lines of `cols` characters each, with `#` for excluded
+    /// areas and a space for available ones. This is meant for debugging.
+    ///
+    /// The columns span the inline size of the zone, and the rows span the block range from the
+    /// top of the zone to `content_bottom`. Each character shows the point at the top of its row
+    /// and the middle of its column.
+    #[cfg(feature = "std")]
+    pub fn render_ascii(&self, rows: usize, cols: usize) -> String {
+        let block_size = cmp::max(self.content_bottom(), Au(1));
+        let mut output = String::with_capacity(rows * (cols + 1));
+        for row in 0..rows {
+            let block_position = Au((row as i64 * block_size.0 as i64 / rows as i64) as i32);
+            let band = self.band_at(block_position).1;
+            let (left_edge, right_edge) = (-band.left, self.inline_size + band.right);
+            for col in 0..cols {
+                let inline_position = (2 * col as i64 + 1) * self.inline_size.0 as i64 /
+                    (2 * cols as i64);
+                let inline_position = Au(inline_position as i32);
+                let excluded = inline_position < left_edge || inline_position >= right_edge;
+                output.push(if excluded { '#' } else { ' ' });
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Returns an iterator over the bands and their block positions, in block order.
+    pub fn bands(&self) -> Bands<'_> {
+        Bands {
+            exclusions: self,
+            next_block_position: Some(self.bands.lower_bound_with(|_, _| Ordering::Less)
+                                                .expect("Exclusions::bands(): No bands!").0),
+        }
+    }
+
+    /// Excludes the margin box of an object placed with `place_with_margins`, given the origin
+    /// and size of its border box.
+    ///
+    /// Like `exclude`, the excluded area extends to the top of the zone and to the edge of the
+    /// zone on the given side.
+    pub fn exclude_with_margins(&mut self,
+                                side: Side,
+                                origin: &Point,
+                                size: &Size,
+                                margins: &Margins) {
+        let (left_margin, right_margin) = self.physical_margins(margins);
+        let margin_box_origin = Point::new(origin.inline - left_margin, origin.block);
+        let margin_box_size = *size + Size::new(left_margin + right_margin, margins.block_end);
+        let size = self.excluded_size(side, &margin_box_origin, &margin_box_size);
+        self.exclude(side, &size);
+    }
+
+    /// Returns the size to give `exclude` to exclude the rectangle with the given origin and size
+    /// along with everything above it and between it and the edge of the zone on the given side.
+    fn excluded_size(&self, side: Side, origin: &Point, size: &Size) -> Size {
+        let inline_size = match self.physical_side(side) {
+            Side::Left => origin.inline + size.inline,
+            Side::Right => self.inline_size - origin.inline,
+        };
+        Size::new(inline_size, origin.block + size.block)
+    }
+
+    /// Excludes a circle with the given center and radius, as for a float with CSS
+    /// `shape-outside: circle()`, approximating it with horizontal slices one CSS pixel tall. See
+    /// `exclude_circle_with_band_size`.
+    pub fn exclude_circle(&mut self, side: Side, center: Point, radius: Au) {
+        self.exclude_circle_with_band_size(side, center, radius, Au::from_px(1))
+    }
+
+    /// Excludes a circle with the given center and radius, approximating it with horizontal
+    /// slices of the given block size. Each slice is excluded out to the widest point of the
+    /// circle within it, so content never overlaps the circle itself.
+    ///
+    /// Like `exclude`, each slice extends to the top of the zone and to the edge of the zone on
+    /// the given side. The slices below the center of the circle taper off toward its bottom,
+    /// but everything above the center is excluded as far as the center is.
+    pub fn exclude_circle_with_band_size(&mut self,
+                                         side: Side,
+                                         center: Point,
+                                         radius: Au,
+                                         band_size: Au) {
+        for size in self.circle_slices(side, center, radius, band_size) {
+            self.exclude(side, &size);
+        }
+    }
+
+    /// Retracts a circle added by `exclude_circle` with the same arguments, returning false if
+    /// there is no such circle.
+    pub fn remove_circle(&mut self, side: Side, center: Point, radius: Au) -> bool {
+        self.remove_circle_with_band_size(side, center, radius, Au::from_px(1))
+    }
+
+    /// Retracts a circle added by `exclude_circle_with_band_size` with the same arguments, slice
+    /// by slice, returning false if any of its slices weren't excluded.
+    pub fn remove_circle_with_band_size(&mut self,
+                                        side: Side,
+                                        center: Point,
+                                        radius: Au,
+                                        band_size: Au)
+                                        -> bool {
+        let mut removed_all = true;
+        for size in self.circle_slices(side, center, radius, band_size) {
+            removed_all &= self.remove_excluded(side, &size);
+        }
+        removed_all
+    }
+
+    /// Returns the sizes to give `exclude` for each slice of a circle, as described in
+    /// `exclude_circle_with_band_size`.
+    fn circle_slices(&self, side: Side, center: Point, radius: Au, band_size: Au) -> Vec<Size> {
+        assert!(band_size > Au::ZERO,
+                "Exclusions::exclude_circle_with_band_size(): Band size must be positive!");
+        let edge = match self.physical_side(side) {
+            Side::Left => center.inline,
+            Side::Right => self.inline_size - center.inline,
+        };
+        let (radius, band_size) = (radius.0 as i64, band_size.0 as i64);
+        let (center_block, bottom) = (center.block.0 as i64, center.block.0 as i64 + radius);
+
+        // The slices entirely above the center are no wider than the one containing it, which
+        // reaches the top of the zone anyway, so start with that one.
+        let mut slices = vec![];
+        let mut block_start = center_block - radius;
+        block_start += (center_block - block_start) / band_size * band_size;
+        while block_start < bottom {
+            let block_end = cmp::min(block_start + band_size, bottom);
+            // The circle is widest at the point of the slice nearest its center.
+            let distance = cmp::max(block_start - center_block, 0);
+            let half_chord = (radius * radius - distance * distance) as u64;
+            let inline_size = edge + Au(half_chord.isqrt() as i32);
+            let block_end_au = Au(cmp::min(block_end, i32::MAX as i64) as i32);
+            if inline_size > Au::ZERO && block_end_au > Au::ZERO {
+                slices.push(Size::new(inline_size, block_end_au));
+            }
+            block_start = block_end;
+        }
+        slices
+    }
+
+    /// Returns the left and right margins, in that order.
+    fn physical_margins(&self, margins: &Margins) -> (Au, Au) {
+        match self.direction {
+            Direction::Ltr => (margins.inline_start, margins.inline_end),
+            Direction::Rtl => (margins.inline_end, margins.inline_start),
+        }
+    }
+
+    /// Returns the side of the zone that objects aligned to the given side go on.
+    fn physical_side(&self, side: Side) -> Side {
+        match self.direction {
+            Direction::Ltr => side,
+            Direction::Rtl => side.opposite(),
+        }
+    }
+
+    /// Returns the band spanning the given block position without restructuring the band map.
+    fn band_at(&self, block_position: Au) -> (Au, &Band) {
+        let &(band_block_position, ref band) =
+            self.bands.lower_bound_with(|&band_block_position, band| {
+                if block_position >= band_block_position + band.length {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                }
+            }).expect("Exclusions::band_at(): Didn't find a band!");
+        (band_block_position, band)
+    }
+
+    /// Calls `f` with the block position and band of each band overlapping the block range
+    /// `[block_position, block_position + block_size)`, in block order, until `f` returns false. A
+    /// zero block size visits the band at `block_position`.
+    fn each_band_in<F>(&self, block_position: Au, block_size: Au, mut f: F)
+                       where F: FnMut(Au, &Band) -> bool {
+        let block_end = block_position.saturating_add(block_size);
+        let mut next_block_position = block_position;
+        loop {
+            let (band_block_position, band) = self.band_at(next_block_position);
+            if !f(band_block_position, band) {
+                return
+            }
+            next_block_position = band_block_position + band.length;
+            if next_block_position >= block_end || next_block_position == Au::MAX {
+                return
+            }
+        }
     }
 
     /// Splits the band spanning the given block position in two at that point.
@@ -278,6 +1141,50 @@ impl Exclusions {
         let lower_band = Band::new(left_size, right_size, floor - block_position);
         self.bands.insert(block_position, lower_band);
     }
+
+    /// Merges the band starting at the given block position into the band directly above it if
+    /// the two bands have the same insets.
+    ///
+    ///  ┌───────────────┐     ┌───────────────┐
+    ///  │               │     │               │
+    ///  ├────────────┬──┘     ├────────────┬──┘
+    ///  │            │        │            │
+    ///  ├┄┄┄┄┄┄┄┄┄┄┄┄┤     →  │            │
+    ///  │            │        │            │
+    ///  ├───┬────────┘        ├───┬────────┘
+    ///  │   │                 │   │
+    ///  ├───┘                 ├───┘
+    ///  │                     │
+    fn merge_with_band_above(&mut self, block_position: Au) {
+        let lower_band = match self.bands.get(&block_position) {
+            Some(band) => *band,
+            None => return,
+        };
+        match self.bands.get_with_mut(|&upper_block_position, band| {
+            if block_position <= upper_block_position {
+                Ordering::Less
+            } else if block_position > upper_block_position + band.length {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        }) {
+            Some(&mut (_, ref mut upper_band)) if upper_band.can_merge_with(&lower_band) => {
+                upper_band.length += lower_band.length;
+            }
+            Some(_) | None => return,
+        }
+        self.bands.remove(&block_position);
+    }
+}
+
+/// Rounds a length to the nearest multiple of `grid`, leaving `Au::MAX` put, since it marks the
+/// bottom of the zone.
+fn snap_to_grid(length: Au, grid: Au) -> Au {
+    if length == Au::MAX {
+        return length
+    }
+    length.round_to_nearest(grid)
 }
 
 fn compare_inline_size(band_block_start: Au,
@@ -287,7 +1194,7 @@ fn compare_inline_size(band_block_start: Au,
                        -> Ordering {
     match exclusion_size.inline.cmp(&band.available_size(inline_size)) {
         Ordering::Less | Ordering::Equal => Ordering::Less,
-        Ordering::Greater if band_block_start + band.length == MAX_AU => Ordering::Equal,
+        Ordering::Greater if band_block_start + band.length == Au::MAX => Ordering::Equal,
         Ordering::Greater => Ordering::Greater,
     }
 }
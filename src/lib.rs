@@ -14,22 +14,42 @@
 //! In practice, this algorithm seems to be O(n) for placement of n floats due to the splay tree
 //! and aggressive merging of bands. Worst case, it is O(n²); however, this is very rare.
 
-#![cfg_attr(test, feature(test))]
+#![no_std]
+#![cfg_attr(all(test, feature = "unstable"), feature(test))]
 
-extern crate app_units;
-#[cfg(test)]
+#[cfg(any(test, feature = "alloc"))]
 #[macro_use]
+extern crate alloc;
+#[cfg(any(test, feature = "std"))]
+extern crate app_units;
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+#[cfg(any(test, feature = "arbitrary"))]
+#[cfg_attr(test, macro_use)]
 extern crate quickcheck;
 #[cfg(test)]
 extern crate rand;
-#[cfg(test)]
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
+#[cfg(all(test, feature = "unstable"))]
 extern crate test as rust_test;
 
+#[cfg(any(test, feature = "std"))]
+pub mod au;
+#[cfg(any(test, feature = "std"))]
 pub mod exclusions;
-mod map;
+#[cfg(any(test, feature = "alloc"))]
+pub mod map;
+#[cfg(any(test, feature = "alloc"))]
 mod node;
-#[cfg(test)]
+#[cfg(any(test, feature = "arbitrary"))]
+mod arbitrary;
+#[cfg(all(test, feature = "unstable"))]
 mod bench;
+#[cfg(feature = "serde")]
+mod serialization;
 #[cfg(test)]
 mod test;
 
@@ -8,38 +8,256 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::borrow::Borrow;
-use std::cell::UnsafeCell;
-use std::cmp::Ordering::{self, Less, Equal, Greater};
-use std::default::Default;
-use std::iter::{FromIterator, IntoIterator};
-use std::mem;
-use std::ops::{Index, IndexMut};
+//! A splay tree map, used to store the bands of exclusions.
 
-use super::node::Node;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::array;
+use core::borrow::Borrow;
+#[cfg(feature = "stats")]
+use core::cell::Cell;
+use core::cmp::{self, Ordering::{self, Less, Equal, Greater}};
+use core::default::Default;
+use core::fmt::{self, Debug, Display, Formatter};
+use core::iter::{FromIterator, IntoIterator};
+use core::mem;
+use core::ops::{Bound, Index, IndexMut, RangeBounds};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use super::node::{Node, subtree_height, subtree_size};
+
+/// Creates a `SplayMap` containing the given key-value pairs.
+///
+/// ```
+/// #[macro_use]
+/// extern crate buoyancy;
+///
+/// use buoyancy::map::SplayMap;
+///
+/// # fn main() {
+/// let map = splaymap! {
+///     1 => "one",
+///     2 => "two",
+/// };
+/// assert_eq!(map[&2], "two");
+///
+/// let empty: SplayMap<u32, &str> = splaymap! {};
+/// assert!(empty.into_iter().next().is_none());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! splaymap {
+    ($($key:expr => $value:expr),* $(,)?) => {
+        {
+            let pairs = [$(($key, $value)),*];
+            let map: $crate::map::SplayMap<_, _> = IntoIterator::into_iter(pairs).collect();
+            map
+        }
+    };
+}
 
 /// The implementation of this splay tree is largely based on the c code at:
 ///     ftp://ftp.cs.cmu.edu/usr/ftp/usr/sleator/splaying/top-down-splay.c
 /// This version of splaying is a top-down splay operation.
+///
+/// A `SplayMap` is `Send` and `Sync` whenever its keys and values are, through the automatic
+/// impls. Every method that splays takes `&mut self`, and methods that take `&self` only read the
+/// tree, so sharing a map between threads is sound. No `unsafe impl` is needed, and one shouldn't
+/// be added: if a method ever splays through `&self` again, the map must stop being `Sync`. With
+/// the `stats` feature, the counters are kept in a `Cell`, so the map is `Send` but not `Sync`.
 pub struct SplayMap<K: Ord, V> {
-    root: UnsafeCell<Option<Box<Node<K, V>>>>,
+    root: Option<Box<Node<K, V>>>,
     size: usize,
+    counters: Counters,
+    splay_on_read: bool,
+}
+
+/// Counts of the work a `SplayMap` has done, as returned by `SplayMap::stats`.
+#[cfg(feature = "stats")]
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Stats {
+    /// The number of rotations done while splaying.
+    pub rotations: u64,
+    /// The number of searches for a key, whether or not they splayed it.
+    pub lookups: u64,
+    /// The number of times a key was compared during a search.
+    pub comparisons: u64,
+}
+
+/// The shape of the tree of a `SplayMap`, as returned by `SplayMap::diagnostics`. Depths count
+/// nodes, so the root is at depth 1. For an empty map, everything is zero.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct TreeStats {
+    /// The number of pairs in the map.
+    pub len: usize,
+    /// The depth of the deepest leaf, which is the height of the tree.
+    pub max_depth: usize,
+    /// The depth of the shallowest leaf.
+    pub min_depth: usize,
+    /// The mean depth of all the nodes, which is the average number of nodes a lookup without
+    /// splaying visits to find a key in the map.
+    pub average_depth: f64,
+}
+
+/// The counters behind `SplayMap::stats`. Without the `stats` feature, this is empty and counting
+/// does nothing.
+#[derive(Default)]
+struct Counters {
+    #[cfg(feature = "stats")]
+    stats: Cell<Stats>,
+}
+
+impl Counters {
+    #[inline]
+    fn count_rotation(&self) {
+        #[cfg(feature = "stats")]
+        self.update(|stats| stats.rotations += 1)
+    }
+
+    #[inline]
+    fn count_lookup(&self) {
+        #[cfg(feature = "stats")]
+        self.update(|stats| stats.lookups += 1)
+    }
+
+    #[inline]
+    fn count_comparison(&self) {
+        #[cfg(feature = "stats")]
+        self.update(|stats| stats.comparisons += 1)
+    }
+
+    #[cfg(feature = "stats")]
+    fn update<F>(&self, f: F) where F: FnOnce(&mut Stats) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+}
+
+/// The error returned by `SplayMap::try_insert` when the key is already in the map.
+pub struct OccupiedError<'a, K, V: 'a> {
+    /// The key that wasn't inserted.
+    pub key: K,
+    /// The value that wasn't inserted.
+    pub value: V,
+    /// The value already in the map for the key.
+    pub existing: &'a mut V,
+}
+
+impl<'a, K: Debug, V: Debug> Debug for OccupiedError<'a, K, V> {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        formatter.debug_struct("OccupiedError")
+                 .field("key", &self.key)
+                 .field("value", &self.value)
+                 .field("existing", &self.existing)
+                 .finish()
+    }
 }
 
+impl<'a, K: Debug, V: Debug> Display for OccupiedError<'a, K, V> {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(formatter,
+               "failed to insert {:?}, key {:?} already exists with value {:?}",
+               self.value,
+               self.key,
+               self.existing)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K: Debug, V: Debug> Error for OccupiedError<'a, K, V> {}
+
 pub struct IntoIter<K, V> {
     cur: Option<Box<Node<K, V>>>,
     remaining: usize,
 }
 
+/// An iterator over references to the pairs of a `SplayMap` in ascending order of key. It doesn't
+/// splay, so it keeps the nodes whose right subtrees it has yet to visit on a stack. It can also
+/// be run backward, using a second stack of the nodes whose left subtrees it has yet to visit.
+pub struct Iter<'a, K: 'a, V: 'a> {
+    stack: Vec<&'a Node<K, V>>,
+    back_stack: Vec<&'a Node<K, V>>,
+    remaining: usize,
+}
+
+/// An iterator over the pairs of a `SplayMap` in ascending order of key, with mutable references
+/// to the values. Like `Iter`, it keeps a stack of the pairs whose right subtrees it has yet to
+/// visit.
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    stack: Vec<PendingMut<'a, K, V>>,
+    remaining: usize,
+}
+
+/// A pair that `IterMut` has yet to yield, along with the right subtree to visit after it.
+type PendingMut<'a, K, V> = (&'a K, &'a mut V, Option<&'a mut Node<K, V>>);
+
+/// An iterator over references to the pairs of a `SplayMap` with keys in a range, in ascending
+/// order of key.
+pub struct Range<'a, K: 'a, V: 'a> {
+    iter: Iter<'a, K, V>,
+}
+
+/// An iterator over the pairs of a `SplayMap` with keys in a range, in ascending order of key,
+/// with mutable references to the values.
+pub struct RangeMut<'a, K: 'a, V: 'a> {
+    iter: IterMut<'a, K, V>,
+}
+
+/// An iterator over the keys of a `SplayMap` in ascending order.
+pub struct Keys<'a, K: 'a, V: 'a> {
+    iter: Iter<'a, K, V>,
+}
+
+/// An iterator over the values of a `SplayMap` in ascending order of key.
+pub struct Values<'a, K: 'a, V: 'a> {
+    iter: Iter<'a, K, V>,
+}
+
+/// An iterator over mutable references to the values of a `SplayMap` in ascending order of key.
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    iter: IterMut<'a, K, V>,
+}
+
+/// An iterator that moves the keys out of a `SplayMap` in ascending order.
+pub struct IntoKeys<K, V> {
+    iter: IntoIter<K, V>,
+}
+
+/// An iterator that moves the values out of a `SplayMap` in ascending order of key.
+pub struct IntoValues<K, V> {
+    iter: IntoIter<K, V>,
+}
+
+/// An iterator that moves the pairs out of a `SplayMap` in ascending order of key, leaving it
+/// empty. The pairs that aren't yielded are dropped along with the iterator.
+pub struct Drain<K, V> {
+    iter: IntoIter<K, V>,
+}
+
 /// Performs a top-down splay operation on a tree rooted at `node`. This will
 /// modify the pointer to contain the new root of the tree once the splay
 /// operation is done. When finished, if `key` is in the tree, it will be at the
 /// root. Otherwise the closest key to the specified key will be at the root.
-fn splay_with<K, V, Q>(mut compare: Q, node: &mut Box<Node<K, V>>)
+///
+/// Subtree sizes and heights are kept up to date much as in Sleator's
+/// top-down-size-splay.c: the nodes hung off the left and right trees on the way
+/// down are counted, and the spines of those trees are fixed up from the bottom
+/// once they are reassembled.
+fn splay_with<K, V, Q>(mut compare: Q, node: &mut Box<Node<K, V>>, counters: &Counters)
                        where K: Ord,
                              Q: FnMut(&K, &V) -> Ordering {
+    counters.count_lookup();
+    let mut compare = |key: &K, value: &V| {
+        counters.count_comparison();
+        compare(key, value)
+    };
     let mut newleft = None;
     let mut newright = None;
+    let (mut l_count, mut r_count) = (0, 0);
 
     // Explicitly grab a new scope so the loans on newleft/newright are
     // terminated before we move out of them.
@@ -60,17 +278,21 @@ fn splay_with<K, V, Q>(mut compare: Q, node: &mut Box<Node<K, V>>)
                     let mut left = node.take_left();
                     // rotate this node right if necessary
                     if compare(&left.key_value.0, &left.key_value.1) == Less {
+                        counters.count_rotation();
                         // A bit odd, but avoids drop glue
                         mem::swap(&mut node.left, &mut left.right);
+                        node.update();
                         mem::swap(&mut left, node);
-                        let none = mem::replace(&mut node.right, Some(left));
+                        let none = node.right.replace(left);
                         match mem::replace(&mut node.left, none) {
                             None => break,
                             Some(l) => left = l,
                         }
                     }
 
-                    mem::forget(mem::replace(r, Some(mem::replace(node, left))));
+                    let linked = mem::replace(node, left);
+                    r_count += 1;
+                    mem::forget(r.replace(linked));
                     let tmp = r;
                     r = &mut tmp.as_mut().unwrap().left;
                 }
@@ -84,15 +306,19 @@ fn splay_with<K, V, Q>(mut compare: Q, node: &mut Box<Node<K, V>>)
                     let mut right = node.take_right();
                     // Rotate right if necessary.
                     if compare(&right.key_value.0, &right.key_value.1) == Greater {
+                        counters.count_rotation();
                         mem::swap(&mut node.right, &mut right.left);
+                        node.update();
                         mem::swap(&mut right, node);
-                        let none = mem::replace(&mut node.left, Some(right));
+                        let none = node.left.replace(right);
                         match mem::replace(&mut node.right, none) {
                             None => break,
                             Some(r) => right = r,
                         }
                     }
-                    mem::forget(mem::replace(l, Some(mem::replace(node, right))));
+                    let linked = mem::replace(node, right);
+                    l_count += 1;
+                    mem::forget(l.replace(linked));
                     let tmp = l;
                     l = &mut tmp.as_mut().unwrap().right;
                 }
@@ -106,45 +332,602 @@ fn splay_with<K, V, Q>(mut compare: Q, node: &mut Box<Node<K, V>>)
     // Optimization to avoid drop glue…
     mem::forget(mem::replace(&mut node.left, newright));
     mem::forget(mem::replace(&mut node.right, newleft));
+
+    update_spine(&mut node.left, l_count, true);
+    update_spine(&mut node.right, r_count, false);
+    node.update();
+}
+
+/// Fills in the sizes and heights of the first `count` nodes on the right spine of the given
+/// tree, or its left spine if `right` is false, after `splay_with` or `SplayMap::bulk_insert`
+/// has hung nodes off it.
+///
+/// These can only be worked out from the bottom up, so the spine is unlinked and reversed in
+/// place, then relinked from the bottom up with each node updated on the way. This needs no
+/// buffer, so splaying doesn't allocate.
+fn update_spine<K, V>(tree: &mut Option<Box<Node<K, V>>>, count: usize, right: bool) {
+    fn spine<K, V>(node: &mut Node<K, V>, right: bool) -> &mut Option<Box<Node<K, V>>> {
+        if right { &mut node.right } else { &mut node.left }
+    }
+
+    if count == 0 {
+        return
+    }
+    // Point each spine node at the one above it instead of the one below it.
+    let (mut rest, mut reversed) = (tree.take(), None);
+    for _ in 0..count {
+        let mut spine_node = rest.expect("update_spine(): Spine too short!");
+        rest = mem::replace(spine(&mut spine_node, right), reversed);
+        reversed = Some(spine_node);
+    }
+    // Then point them back down, starting from the bottom, so the subtree below each spine node
+    // is finished by the time it is reached.
+    while let Some(mut spine_node) = reversed {
+        reversed = mem::replace(spine(&mut spine_node, right), rest);
+        spine_node.update();
+        rest = Some(spine_node);
+    }
+    *tree = rest;
+}
+
+/// Inserts a pair whose key isn't in the given tree as its new root, next to the old root, which
+/// must be the closest key to it as after a splay.
+fn insert_at_root_of<K: Ord, V>(tree: &mut Option<Box<Node<K, V>>>, key: K, value: V) {
+    match *tree {
+        Some(ref mut root) => {
+            /* TODO: would unsafety help perf here? */
+            if key < root.key_value.0 {
+                let left = root.pop_left();
+                let new = Node::new(key, value, left, None);
+                let mut prev = mem::replace(root, new);
+                prev.update();
+                root.right = Some(prev);
+            } else {
+                let right = root.pop_right();
+                let new = Node::new(key, value, None, right);
+                let mut prev = mem::replace(root, new);
+                prev.update();
+                root.left = Some(prev);
+            }
+            root.update();
+        }
+        None => *tree = Some(Node::new(key, value, None, None)),
+    }
 }
 
-fn splay_with_key<K, V, Q: ?Sized>(key: &Q, node: &mut Box<Node<K, V>>)
-                                   where K: Ord + Borrow<Q>, Q: Ord {
-    splay_with(|other_key, _| key.cmp(other_key.borrow()), node)
+fn splay_with_key<K, V, Q>(key: &Q, node: &mut Box<Node<K, V>>, counters: &Counters)
+                           where K: Ord + Borrow<Q>, Q: Ord + ?Sized {
+    splay_with(|other_key, _| key.cmp(other_key.borrow()), node, counters)
 }
 
-fn lower_bound_with<K, V, Q>(mut compare: Q, node: &Box<Node<K, V>>) -> Option<&(K, V)>
+fn lower_bound_with<K, V, Q>(mut compare: Q, mut node: &Option<Box<Node<K, V>>>)
+                             -> Option<&(K, V)>
                              where K: Ord, Q: FnMut(&K, &V) -> Ordering {
-    match compare(&node.key_value.0, &node.key_value.1) {
-        Less => {
-            if let Some(ref left) = node.left {
-                if let Some(key_value) = lower_bound_with(compare, left) {
-                    return Some(key_value)
+    let mut bound = None;
+    while let Some(ref cur) = *node {
+        match compare(&cur.key_value.0, &cur.key_value.1) {
+            Less => {
+                bound = Some(&cur.key_value);
+                node = &cur.left;
+            }
+            Greater => node = &cur.right,
+            Equal => return Some(&cur.key_value),
+        }
+    }
+    bound
+}
+
+fn upper_bound_with<K, V, Q>(mut compare: Q, mut node: &Option<Box<Node<K, V>>>)
+                             -> Option<&(K, V)>
+                             where K: Ord, Q: FnMut(&K, &V) -> Ordering {
+    let mut bound = None;
+    while let Some(ref cur) = *node {
+        match compare(&cur.key_value.0, &cur.key_value.1) {
+            Less => node = &cur.left,
+            Greater => {
+                bound = Some(&cur.key_value);
+                node = &cur.right;
+            }
+            Equal => return Some(&cur.key_value),
+        }
+    }
+    bound
+}
+
+/// Checks a node whose children are both intact against them for `SplayMap::check_invariants`,
+/// returning what is wrong with it if anything is. The keys are compared with the largest key on
+/// the left and the smallest on the right, which are found by walking down the spines of the
+/// children; each node is on only one such spine, so this takes O(n) time over the whole tree.
+fn check_node<K: Ord, V>(node: &Node<K, V>) -> Option<&'static str> {
+    if last_pair(&node.left).is_some_and(|(key, _)| *key >= node.key_value.0) ||
+            first_pair(&node.right).is_some_and(|(key, _)| *key <= node.key_value.0) {
+        return Some("Keys are out of order!")
+    }
+    if node.left.as_ref().is_some_and(|left| left.size == 0) ||
+            node.right.as_ref().is_some_and(|right| right.size == 0) ||
+            node.size != 1 + subtree_size(&node.left) + subtree_size(&node.right) {
+        return Some("Subtree size is wrong!")
+    }
+    if node.height != 1 + cmp::max(subtree_height(&node.left), subtree_height(&node.right)) {
+        return Some("Subtree height is wrong!")
+    }
+    None
+}
+
+/// Copies a tree, keeping its shape. Like `map_tree`, this walks the tree in order with a
+/// heap-allocated stack, so degenerate trees don't exhaust the call stack.
+fn clone_tree<K, V>(root: &Option<Box<Node<K, V>>>) -> Option<Box<Node<K, V>>>
+                    where K: Clone, V: Clone {
+    enum Frame<'a, K: 'a, V: 'a> {
+        // A node whose left subtree is being copied.
+        Left(&'a Node<K, V>),
+        // A node, along with the copy of its left subtree, whose right subtree is being copied.
+        Right(&'a Node<K, V>, Option<Box<Node<K, V>>>),
+    }
+
+    let (mut stack, mut cur) = (vec![], root.as_deref());
+    loop {
+        while let Some(node) = cur {
+            cur = node.left.as_deref();
+            stack.push(Frame::Left(node));
+        }
+        // Pop frames until one needs its right subtree copied, assembling the finished subtrees
+        // on the way.
+        let mut copied = None;
+        loop {
+            match stack.pop() {
+                None => return copied,
+                Some(Frame::Left(node)) => {
+                    stack.push(Frame::Right(node, copied));
+                    cur = node.right.as_deref();
+                    break
                 }
+                Some(Frame::Right(node, left)) => {
+                    let (ref key, ref value) = node.key_value;
+                    copied = Some(Node::new(key.clone(), value.clone(), left, copied));
+                }
+            }
+        }
+    }
+}
+
+/// Frees a tree in O(1) extra space (including the stack) by rotating left children up until
+/// each node can be dropped without recursing.
+fn destroy<K, V>(mut cur: Option<Box<Node<K, V>>>) {
+    while let Some(mut node) = cur {
+        cur = match node.pop_left() {
+            Some(mut left) => {
+                node.left = left.pop_right();
+                left.right = Some(node);
+                Some(left)
             }
-            Some(&node.key_value)
+            None => node.pop_right(),
+        };
+    }
+}
+
+/// Builds a perfectly balanced tree out of the next `count` nodes of `iter`, which must be sorted
+/// by key, overwriting their children. The recursion is only logarithmic in `count` deep.
+fn build_balanced<K, V, I>(iter: &mut I, count: usize) -> Option<Box<Node<K, V>>>
+                           where I: Iterator<Item = Box<Node<K, V>>> {
+    if count == 0 {
+        return None
+    }
+    let left = build_balanced(iter, count / 2);
+    let mut node = iter.next().expect("build_balanced(): Ran out of nodes!");
+    node.left = left;
+    node.right = build_balanced(iter, count - count / 2 - 1);
+    node.update();
+    Some(node)
+}
+
+/// Builds a tree with the same shape and keys as the given one, with each value replaced by the
+/// result of `f`. The tree is walked in order with a heap-allocated stack, so `f` sees the values
+/// in key order and degenerate trees don't exhaust the call stack.
+fn map_tree<K, V, W, F>(root: Option<Box<Node<K, V>>>, mut f: F) -> Option<Box<Node<K, W>>>
+                        where F: FnMut(V) -> W {
+    enum Frame<K, V, W> {
+        // A node whose left subtree is being mapped.
+        Left(Box<Node<K, V>>),
+        // A mapped pair, along with its mapped left subtree, whose right subtree is being mapped.
+        Right(K, W, Option<Box<Node<K, W>>>),
+    }
+
+    let (mut stack, mut cur) = (vec![], root);
+    loop {
+        while let Some(mut node) = cur {
+            cur = node.pop_left();
+            stack.push(Frame::Left(node));
         }
-        Greater => {
-            match node.right {
-                Some(ref right) => lower_bound_with(compare, right),
-                None => None,
+        // Pop frames until one needs its right subtree mapped, assembling the finished subtrees
+        // on the way.
+        let mut mapped = None;
+        loop {
+            match stack.pop() {
+                None => return mapped,
+                Some(Frame::Left(node)) => {
+                    let Node { key_value: (key, value), right, .. } = *node;
+                    let value = f(value);
+                    stack.push(Frame::Right(key, value, mapped));
+                    cur = right;
+                    break
+                }
+                Some(Frame::Right(key, value, left)) => {
+                    mapped = Some(Node::new(key, value, left, mapped));
+                }
             }
         }
-        Equal => Some(&node.key_value),
     }
 }
 
+/// Returns true if the key lies after the given start bound of a range.
+fn is_after_start<Q>(key: &Q, start: Bound<&Q>) -> bool where Q: Ord + ?Sized {
+    match start {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true,
+    }
+}
+
+/// Returns true if the key lies before the given end bound of a range.
+fn is_before_end<Q>(key: &Q, end: Bound<&Q>) -> bool where Q: Ord + ?Sized {
+    match end {
+        Bound::Included(end) => key <= end,
+        Bound::Excluded(end) => key < end,
+        Bound::Unbounded => true,
+    }
+}
+
+/// Returns the pair with the smallest key in the given subtree.
+fn first_pair<K, V>(mut node: &Option<Box<Node<K, V>>>) -> Option<&(K, V)> {
+    let mut pair = None;
+    while let Some(ref cur) = *node {
+        pair = Some(&cur.key_value);
+        node = &cur.left;
+    }
+    pair
+}
+
+/// Returns the pair with the largest key in the given subtree.
+fn last_pair<K, V>(mut node: &Option<Box<Node<K, V>>>) -> Option<&(K, V)> {
+    let mut pair = None;
+    while let Some(ref cur) = *node {
+        pair = Some(&cur.key_value);
+        node = &cur.right;
+    }
+    pair
+}
+
+/// Splays the successor of the root of the tree up to the root, returning false if there is no
+/// successor.
+fn splay_successor<K: Ord, V>(root: &mut Box<Node<K, V>>, counters: &Counters) -> bool {
+    let mut successor = match root.pop_right() {
+        Some(right) => right,
+        None => return false,
+    };
+    // This leaves the smallest node of the right subtree at its root, with no left child.
+    splay_with(|_, _| Less, &mut successor, counters);
+    root.update();
+    let prev = mem::replace(root, successor);
+    root.left = Some(prev);
+    root.update();
+    true
+}
+
+/// Splays the predecessor of the root of the tree up to the root, returning false if there is no
+/// predecessor.
+fn splay_predecessor<K: Ord, V>(root: &mut Box<Node<K, V>>, counters: &Counters) -> bool {
+    let mut predecessor = match root.pop_left() {
+        Some(left) => left,
+        None => return false,
+    };
+    splay_with(|_, _| Greater, &mut predecessor, counters);
+    root.update();
+    let prev = mem::replace(root, predecessor);
+    root.right = Some(prev);
+    root.update();
+    true
+}
+
 impl<K: Ord, V> SplayMap<K, V> {
     pub fn new() -> SplayMap<K, V> {
-        SplayMap { root: UnsafeCell::new(None), size: 0 }
+        SplayMap { root: None, size: 0, counters: Counters::default(), splay_on_read: true }
+    }
+
+    /// Creates a map out of key-value pairs that are already sorted by key. This builds a
+    /// balanced tree in O(n) time, instead of splaying once per pair as `collect` would.
+    ///
+    /// Panics if the keys aren't strictly increasing.
+    pub fn from_sorted_iter<I>(iter: I) -> SplayMap<K, V> where I: IntoIterator<Item = (K, V)> {
+        let mut nodes: Vec<Box<Node<K, V>>> = vec![];
+        for (key, value) in iter {
+            if let Some(last) = nodes.last() {
+                assert!(last.key_value.0 < key,
+                        "SplayMap::from_sorted_iter(): Keys aren't strictly increasing!");
+            }
+            nodes.push(Node::new(key, value, None, None));
+        }
+        let size = nodes.len();
+        let root = build_balanced(&mut nodes.into_iter(), size);
+        let mut map = SplayMap { root, size, counters: Counters::default(), splay_on_read: true };
+        map.assert_consistent();
+        map
+    }
+
+    /// Panics if the structure of the tree is inconsistent: if the number of nodes differs from
+    /// `len`, if a node's subtree size or height is wrong, or if the keys aren't in strictly
+    /// increasing order from left to right.
+    ///
+    /// This walks the tree in O(n) time and O(1) space, in the style of a Morris traversal: the
+    /// links on the path from the root are reversed on the way down and restored on the way back
+    /// up, so it is safe to call on degenerate trees and doesn't allocate. That is why it needs
+    /// `&mut self`, though the tree is left exactly as it was.
+    pub fn check_invariants(&mut self) {
+        // Marks the nodes whose right subtree is being walked, whose link to their parent is in
+        // `right` rather than `left`. Heights never come close to needing this bit.
+        const WALKING_RIGHT: u32 = 1 << 31;
+
+        let (mut cur, mut up) = (self.root_mut().take(), None);
+        let (mut count, mut failure) = (0, None);
+        'walk: loop {
+            while let Some(mut node) = cur {
+                cur = mem::replace(&mut node.left, up);
+                up = Some(node);
+            }
+            // Climb back up, reattaching each finished subtree to its parent, until reaching a
+            // parent whose right subtree is still to be walked.
+            let mut done = None;
+            loop {
+                let mut parent: Box<Node<K, V>> = match up {
+                    Some(parent) => parent,
+                    None => {
+                        *self.root_mut() = done;
+                        break 'walk
+                    }
+                };
+                if parent.height & WALKING_RIGHT != 0 {
+                    parent.height &= !WALKING_RIGHT;
+                    up = mem::replace(&mut parent.right, done);
+                    done = Some(parent);
+                    continue
+                }
+                up = mem::replace(&mut parent.left, done);
+                count += 1;
+                if failure.is_none() {
+                    failure = check_node(&parent);
+                }
+                parent.height |= WALKING_RIGHT;
+                cur = mem::replace(&mut parent.right, up);
+                up = Some(parent);
+                break
+            }
+        }
+        if let Some(message) = failure {
+            panic!("SplayMap::check_invariants(): {}", message)
+        }
+        assert_eq!(count, self.size, "SplayMap::check_invariants(): Size is wrong!");
+    }
+
+    /// Checks the invariants of the tree after a mutation. This is O(n), so it only runs in the
+    /// crate's own tests, or downstream with the `check-invariants` feature.
+    fn assert_consistent(&mut self) {
+        if cfg!(any(test, feature = "check-invariants")) {
+            self.check_invariants()
+        }
     }
 
     /// Moves all values out of this map, transferring ownership to the given
     /// iterator.
+    #[allow(clippy::should_implement_trait)]
     pub fn into_iter(mut self) -> IntoIter<K, V> {
         IntoIter { cur: self.root_mut().take(), remaining: self.size }
     }
 
+    /// Moves all pairs out of this map in ascending order of key, leaving it empty but still
+    /// usable. The map is emptied as soon as this is called, even if the iterator is dropped early.
+    pub fn drain(&mut self) -> Drain<K, V> {
+        let iter = IntoIter { cur: self.root_mut().take(), remaining: self.size };
+        self.size = 0;
+        Drain { iter }
+    }
+
+    /// Returns the pair with the smallest key, or `None` if the map is empty. This walks down the
+    /// left spine of the tree without splaying.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        first_pair(self.root_ref()).map(|(key, value)| (key, value))
+    }
+
+    /// Returns the pair with the largest key, or `None` if the map is empty. This walks down the
+    /// right spine of the tree without splaying.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        last_pair(self.root_ref()).map(|(key, value)| (key, value))
+    }
+
+    /// Returns an iterator over the pairs in this map, in ascending order of key. It doesn't splay,
+    /// and it takes extra space proportional to the height of the tree.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.iter_within::<K>(Bound::Unbounded, Bound::Unbounded, self.size)
+    }
+
+    /// Returns an iterator over the pairs in this map in ascending order of key, with mutable
+    /// references to the values. Like `iter`, it doesn't splay.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let mut iter = IterMut { stack: vec![], remaining: self.size };
+        iter.push_left_spine(self.root.as_deref_mut());
+        iter
+    }
+
+    /// Returns an iterator over the keys in this map, in ascending order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Returns an iterator over the values in this map, in ascending order of key.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { iter: self.iter() }
+    }
+
+    /// Moves the keys out of this map, in ascending order.
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys { iter: self.into_iter() }
+    }
+
+    /// Moves the values out of this map, in ascending order of key.
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues { iter: self.into_iter() }
+    }
+
+    /// Returns an iterator over mutable references to the values in this map, in ascending order
+    /// of key.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { iter: self.iter_mut() }
+    }
+
+    /// Returns an iterator over the pairs with keys greater than or equal to `key`, in ascending
+    /// order of key. The iterator starts at the smallest such key, found by walking down the tree
+    /// once without splaying, so the pairs before it are never visited.
+    pub fn iter_from<Q>(&self, key: &Q) -> Iter<'_, K, V> where K: Borrow<Q>, Q: Ord + ?Sized {
+        let count = self.size - self.count_before(key, false);
+        self.iter_within(Bound::Included(key), Bound::Unbounded, count)
+    }
+
+    /// Returns an iterator over the pairs with keys in the given range, in ascending order of key.
+    /// Like `iter_from`, it starts at the first key in the range without visiting the ones before
+    /// it, and it stops after the number of pairs that `count_range` finds. Run backward, it starts
+    /// at the last key in the range instead. A range whose start is after its end is empty.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+                       where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let count = self.count_range((range.start_bound(), range.end_bound()));
+        Range { iter: self.iter_within(range.start_bound(), range.end_bound(), count) }
+    }
+
+    /// Returns an iterator over the pairs with keys in the given range, in ascending order of key,
+    /// with mutable references to the values. Like `range`, it doesn't splay.
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+                           where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let count = self.count_range((range.start_bound(), range.end_bound()));
+        let mut iter = IterMut { stack: vec![], remaining: count };
+        iter.push_after_start(self.root.as_deref_mut(), range.start_bound());
+        RangeMut { iter }
+    }
+
+    /// Returns an iterator over the `count` pairs starting with the first key that lies after
+    /// the given start bound, or, going backward, with the last key that lies before the given end
+    /// bound.
+    fn iter_within<Q>(&self, start: Bound<&Q>, end: Bound<&Q>, count: usize) -> Iter<'_, K, V>
+                      where K: Borrow<Q>, Q: Ord + ?Sized {
+        let (mut stack, mut cur) = (vec![], self.root_ref().as_deref());
+        while let Some(node) = cur {
+            if is_after_start(node.key_value.0.borrow(), start) {
+                stack.push(node);
+                cur = node.left.as_deref();
+            } else {
+                cur = node.right.as_deref();
+            }
+        }
+        let (mut back_stack, mut cur) = (vec![], self.root_ref().as_deref());
+        while let Some(node) = cur {
+            if is_before_end(node.key_value.0.borrow(), end) {
+                back_stack.push(node);
+                cur = node.right.as_deref();
+            } else {
+                cur = node.left.as_deref();
+            }
+        }
+        Iter { stack, back_stack, remaining: count }
+    }
+
+    /// Returns a copy of the pairs in this map, in ascending order of key.
+    pub fn to_sorted_vec(&self) -> Vec<(K, V)> where K: Clone, V: Clone {
+        let mut pairs = Vec::with_capacity(self.size);
+        pairs.extend(self.iter().map(|(key, value)| (key.clone(), value.clone())));
+        pairs
+    }
+
+    /// Calls `f` on each pair in ascending order of key, threading an accumulator through the calls
+    /// as `Iterator::fold` does, and returns its final value. The values can be changed along the
+    /// way. Like `iter_mut`, this doesn't splay.
+    pub fn fold_values_mut<A, F>(&mut self, init: A, mut f: F) -> A
+                                 where F: FnMut(A, &K, &mut V) -> A {
+        self.iter_mut().fold(init, |accumulator, (key, value)| f(accumulator, key, value))
+    }
+
+    /// Moves the pairs of this map into a vector, in ascending order of key.
+    pub fn into_sorted_vec(self) -> Vec<(K, V)> {
+        let mut pairs = Vec::with_capacity(self.size);
+        pairs.extend(self.into_iter());
+        pairs
+    }
+
+    /// Returns the number of elements in the map.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns true if the map contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the number of rotations, lookups, and comparisons this map has done since it was
+    /// created. Only available with the `stats` feature, which also keeps the map from being
+    /// `Sync`, since `get_no_splay` counts through a shared reference.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> Stats {
+        self.counters.stats.get()
+    }
+
+    /// Measures the depths of the nodes in the tree, to tell how far from balanced it is. This
+    /// walks the whole tree with a heap-allocated stack without splaying, so it takes O(n) time.
+    pub fn diagnostics(&self) -> TreeStats {
+        let mut stats = TreeStats { len: self.size, ..TreeStats::default() };
+        let (mut stack, mut total_depth) = (vec![], 0);
+        stack.extend(self.root_ref().as_deref().map(|root| (root, 1)));
+        while let Some((node, depth)) = stack.pop() {
+            total_depth += depth;
+            if node.left.is_none() && node.right.is_none() {
+                stats.max_depth = cmp::max(stats.max_depth, depth);
+                if stats.min_depth == 0 || depth < stats.min_depth {
+                    stats.min_depth = depth;
+                }
+            }
+            stack.extend(node.left.as_deref().map(|left| (left, depth + 1)));
+            stack.extend(node.right.as_deref().map(|right| (right, depth + 1)));
+        }
+        if self.size > 0 {
+            stats.average_depth = total_depth as f64 / self.size as f64;
+        }
+        stats
+    }
+
+    /// Returns the number of nodes on the longest path from the root of the tree to a leaf, or
+    /// zero if the map is empty. This is tracked in the nodes, so it takes O(1) time.
+    pub fn height(&self) -> usize {
+        subtree_height(self.root_ref()) as usize
+    }
+
+    /// Returns true if the tree is more than four times as tall as a perfectly balanced tree of
+    /// the same size would be. Lookups of the deepest keys in such a tree are slow, though
+    /// splaying them flattens the tree out again.
+    pub fn is_degenerate(&self) -> bool {
+        let balanced_height = (usize::BITS - self.size.leading_zeros()) as usize;
+        self.height() > 4 * balanced_height
+    }
+
+    /// Rebuilds the tree into a perfectly balanced one in O(n) time, keeping the same pairs. This
+    /// is worth doing when the tree has degenerated and will be read without splaying, as by
+    /// `peek` or `iter`. The nodes are relinked rather than reallocated, and neither taking the
+    /// tree apart nor building it back up recurses deeply.
+    pub fn rebalance(&mut self) {
+        let size = self.size;
+        let mut iter = IntoIter { cur: self.root_mut().take(), remaining: size };
+        let mut nodes = (0..size).map(|_| {
+            iter.next_node().expect("SplayMap::rebalance(): Ran out of nodes!")
+        });
+        *self.root_mut() = build_balanced(&mut nodes, size);
+        self.assert_consistent();
+    }
+
     /// Clears the tree in O(1) extra space (including the stack). This is
     /// necessary to prevent stack exhaustion with extremely large trees.
     pub fn clear(&mut self) {
@@ -156,66 +939,210 @@ impl<K: Ord, V> SplayMap<K, V> {
             // ignore, drop the values (and the node)
         }
         self.size = 0;
+        self.assert_consistent();
+    }
+
+    /// Retains only the pairs for which `f` returns true, visiting them in key order. If every
+    /// pair is retained, the tree is left as it is. Otherwise, the nodes of the remaining pairs
+    /// are relinked into a balanced tree without being reallocated. This takes O(n) time.
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&K, &mut V) -> bool {
+        let keep: Vec<bool> = self.iter_mut().map(|(key, value)| f(key, value)).collect();
+        let count = keep.iter().filter(|&&keep| keep).count();
+        if count == self.size {
+            return
+        }
+
+        let mut iter = IntoIter { cur: self.root_mut().take(), remaining: self.size };
+        let mut nodes = keep.into_iter().filter_map(|keep| {
+            let node = iter.next_node().expect("SplayMap::retain(): Ran out of nodes!");
+            if keep { Some(node) } else { None }
+        });
+        *self.root_mut() = build_balanced(&mut nodes, count);
+        self.size = count;
+        self.assert_consistent();
+    }
+
+    /// Retains only the pairs whose keys `f` returns true for.
+    pub fn retain_keys<F>(&mut self, mut f: F) where F: FnMut(&K) -> bool {
+        self.retain(|key, _| f(key))
+    }
+
+    /// Consumes the map and returns one with the same keys, and each value replaced by the result
+    /// of `f`. The new tree has the same shape as this one, so nothing is splayed or compared.
+    /// `f` is called on the values in key order.
+    pub fn map_values<W, F>(mut self, f: F) -> SplayMap<K, W> where F: FnMut(V) -> W {
+        let root = map_tree(self.root_mut().take(), f);
+        let mut map = SplayMap {
+            root,
+            size: self.size,
+            counters: Counters::default(),
+            splay_on_read: self.splay_on_read,
+        };
+        map.assert_consistent();
+        map
     }
 
-    /// Return a reference to the value corresponding to the key
-    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
-        where K: Borrow<Q>, Q: Ord,
+    /// Return a reference to the value corresponding to the key, splaying it to the root so that
+    /// lookups of nearby keys are fast.
+    ///
+    /// Splay trees are self-modifying, so this takes `&mut self`. An earlier version of this
+    /// method splayed through an `UnsafeCell` under `&self`, but that moved nodes around while
+    /// references returned by previous lookups were still alive, which is undefined behavior. Use
+    /// `get_no_splay` (or indexing) to look keys up through a shared reference.
+    ///
+    /// If splaying on reads is turned off with `set_splay_on_read`, this doesn't splay.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>, Q: Ord + ?Sized,
     {
-        // Splay trees are self-modifying, so they can't exactly operate with
-        // the immutable self given by the Map interface for this method. It can
-        // be guaranteed, however, that the callers of this method are not
-        // modifying the tree, they may just be rotating it. Each node is a
-        // pointer on the heap, so we know that none of the pointers inside
-        // these nodes (the value returned from this function) will be moving.
-        //
-        // With this in mind, we can unsafely use a mutable version of this tree
-        // to invoke the splay operation and return a pointer to the inside of
-        // one of the nodes (the pointer won't be deallocated or moved).
-        //
-        // However I'm not entirely sure whether this works with iteration or
-        // not. Arbitrary lookups can occur during iteration, and during
-        // iteration there's some form of "stack" remembering the nodes that
-        // need to get visited. I don't believe that it's safe to allow lookups
-        // while the tree is being iterated. Right now there are no iterators
-        // exposed on this splay tree implementation, and more thought would be
-        // required if there were.
-        unsafe {
-            match *self.root.get() {
-                Some(ref mut root) => {
-                    splay_with_key(key, root);
-                    if key == root.key_value.0.borrow() {
-                        return Some(&root.key_value.1);
-                    }
-                    None
-                }
-                None => None,
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Returns true if the map contains the given key. Like `get`, this splays the key (or the
+    /// closest one to it) to the root, restructuring the tree, so it takes `&mut self`, unless
+    /// splaying on reads is turned off.
+    pub fn contains_key<Q>(&mut self, key: &Q) -> bool where K: Borrow<Q>, Q: Ord + ?Sized {
+        if !self.splay_on_read {
+            return self.get_no_splay(key).is_some()
+        }
+        match self.root {
+            Some(ref mut root) => {
+                splay_with_key(key, root, &self.counters);
+                key == root.key_value.0.borrow()
+            }
+            None => false,
+        }
+    }
+
+    /// Return a reference to the value corresponding to the key without restructuring the tree.
+    /// This is a plain binary search, so it takes time proportional to the depth of the key.
+    pub fn get_no_splay<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>, Q: Ord + ?Sized,
+    {
+        self.counters.count_lookup();
+        let mut cur = self.root_ref().as_ref();
+        while let Some(node) = cur {
+            self.counters.count_comparison();
+            match key.cmp(node.key_value.0.borrow()) {
+                Less => cur = node.left.as_ref(),
+                Greater => cur = node.right.as_ref(),
+                Equal => return Some(&node.key_value.1),
             }
         }
+        None
+    }
+
+    /// Returns a reference to the value corresponding to the key without restructuring the tree,
+    /// like `get_no_splay`. This suits read-heavy workloads that only have a shared reference.
+    ///
+    /// The trade-off is that the tree doesn't adjust itself to the lookups: keys that are looked
+    /// up often stay as deep as they were, so the amortized O(log n) bound of `get` doesn't hold,
+    /// and a degenerate tree stays degenerate.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V> where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.get_no_splay(key)
+    }
+
+    /// Like `get_no_splay`, but returns a mutable reference.
+    fn get_mut_no_splay<Q>(&mut self, key: &Q) -> Option<&mut V>
+                           where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.counters.count_lookup();
+        let mut cur = self.root.as_deref_mut();
+        while let Some(node) = cur {
+            self.counters.count_comparison();
+            match key.cmp(node.key_value.0.borrow()) {
+                Less => cur = node.left.as_deref_mut(),
+                Greater => cur = node.right.as_deref_mut(),
+                Equal => return Some(&mut node.key_value.1),
+            }
+        }
+        None
+    }
+
+    /// Sets whether `get`, `get_mut`, and `contains_key` splay the key they look up to the root.
+    /// This is on by default. Turning it off makes them plain binary searches, which is cheaper
+    /// when the keys looked up are spread evenly so splaying wouldn't pay off. Inserting and
+    /// removing keys still splays either way.
+    pub fn set_splay_on_read(&mut self, enabled: bool) {
+        self.splay_on_read = enabled;
     }
 
     /// Return a mutable reference to the value corresponding to the key
-    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
-        where K: Borrow<Q>, Q: Ord,
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>, Q: Ord + ?Sized,
     {
-        match *self.root_mut() {
-            None => { return None; }
+        if !self.splay_on_read {
+            return self.get_mut_no_splay(key)
+        }
+        match self.root {
+            None => None,
             Some(ref mut root) => {
-                splay_with_key(key, root);
+                splay_with_key(key, root, &self.counters);
                 if key == root.key_value.0.borrow() {
-                    return Some(&mut root.key_value.1);
+                    Some(&mut root.key_value.1)
+                } else {
+                    None
                 }
-                return None;
             }
         }
     }
 
+    /// Returns mutable references to the values for all of the given keys at once, in the same
+    /// order, or `None` if any key isn't in the map or any two keys are equal. The keys are found
+    /// without splaying, since splaying for one key could move the nodes found for the others.
+    ///
+    /// The keys are sorted and looked up together in one walk down the tree, which splits each
+    /// node into its value and its two subtrees and hands the keys on to the subtrees they lie in.
+    /// The references are disjoint because they come from distinct nodes, so no unsafe code is
+    /// needed.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+                                           where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut order: [usize; N] = array::from_fn(|index| index);
+        order.sort_unstable_by(|&a, &b| keys[a].cmp(keys[b]));
+        if order.windows(2).any(|pair| keys[pair[0]] == keys[pair[1]]) {
+            return None
+        }
+
+        for _ in 0..N {
+            self.counters.count_lookup();
+        }
+        let counters = &self.counters;
+        let mut values: [Option<&mut V>; N] = array::from_fn(|_| None);
+        let mut stack = vec![];
+        if let Some(root) = self.root.as_deref_mut() {
+            stack.push((root, &order[..]));
+        }
+        while let Some((node, order)) = stack.pop() {
+            let Node { key_value: (ref key, ref mut value), ref mut left, ref mut right, .. } =
+                *node;
+            let key = key.borrow();
+            let compare = |index: &usize| {
+                counters.count_comparison();
+                keys[*index].cmp(key)
+            };
+            let less = order.partition_point(|index| compare(index) == Less);
+            let not_greater = less + order[less..].partition_point(|index| compare(index) == Equal);
+            if not_greater > less {
+                values[order[less]] = Some(value);
+            }
+            if let (Some(left), false) = (left.as_deref_mut(), less == 0) {
+                stack.push((left, &order[..less]));
+            }
+            if let (Some(right), false) = (right.as_deref_mut(), not_greater == order.len()) {
+                stack.push((right, &order[not_greater..]));
+            }
+        }
+
+        if values.iter().any(Option::is_none) {
+            return None
+        }
+        Some(values.map(Option::unwrap))
+    }
+
     pub fn get_with_mut<Q>(&mut self, mut compare: Q) -> Option<&mut (K, V)>
                            where Q: FnMut(&K, &V) -> Ordering {
-        match *self.root_mut() {
+        match self.root {
             None => None,
             Some(ref mut root) => {
-                splay_with(&mut compare, root);
+                splay_with(&mut compare, root, &self.counters);
                 if compare(&root.key_value.0, &root.key_value.1) == Equal {
                     Some(&mut root.key_value)
                 } else {
@@ -225,86 +1152,718 @@ impl<K: Ord, V> SplayMap<K, V> {
         }
     }
 
+    /// Returns the pair with the smallest key for which `compare` returns `Less` or `Equal`.
+    /// `compare` orders the sought position relative to each key, so with
+    /// `|key, _| target.cmp(key)` this finds the smallest key that is at least `target`. This
+    /// doesn't splay.
     pub fn lower_bound_with<Q>(&self, compare: Q) -> Option<&(K, V)>
                                where Q: FnMut(&K, &V) -> Ordering {
-        self.root_ref().as_ref().and_then(|root| lower_bound_with(compare, root))
+        lower_bound_with(compare, self.root_ref())
     }
 
-    /// Insert a key-value pair from the map. If the key already had a value
-    /// present in the map, that value is returned. Otherwise None is returned.
-    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        match self.root_mut() {
-            &mut Some(ref mut root) => {
-                splay_with_key(&key, root);
-
-                match key.cmp(&root.key_value.0) {
-                    Equal => {
-                        let old = mem::replace(&mut root.key_value.1, value);
-                        return Some(old);
-                    }
-                    /* TODO: would unsafety help perf here? */
-                    Less => {
-                        let left = root.pop_left();
-                        let new = Node::new(key, value, left, None);
-                        let prev = mem::replace(root, new);
-                        root.right = Some(prev);
-                    }
-                    Greater => {
-                        let right = root.pop_right();
-                        let new = Node::new(key, value, None, right);
-                        let prev = mem::replace(root, new);
-                        root.left = Some(prev);
-                    }
-                }
-            }
-            slot => {
-                *slot = Some(Node::new(key, value, None, None));
-            }
-        }
-        self.size += 1;
-        return None;
+    /// Returns the pair with the largest key for which `compare` returns `Greater` or `Equal`,
+    /// the mirror image of `lower_bound_with`. With `|key, _| target.cmp(key)` this finds the
+    /// largest key that is at most `target`. This doesn't splay.
+    pub fn upper_bound_with<Q>(&self, compare: Q) -> Option<&(K, V)>
+                               where Q: FnMut(&K, &V) -> Ordering {
+        upper_bound_with(compare, self.root_ref())
     }
 
-    /// Removes a key from the map, returning the value at the key if the key
-    /// was previously in the map.
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
-        where K: Borrow<Q>, Q: Ord
-    {
-        match *self.root_mut() {
+    /// Returns the pair with the largest key less than `key`, whether or not `key` is in the map.
+    /// This doesn't splay.
+    pub fn predecessor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+                          where K: Borrow<Q>, Q: Ord + ?Sized {
+        let compare = |other_key: &K, _: &V| {
+            if key > other_key.borrow() { Greater } else { Less }
+        };
+        upper_bound_with(compare, self.root_ref()).map(|(key, value)| (key, value))
+    }
+
+    /// Returns the pair with the smallest key greater than `key`, whether or not `key` is in the
+    /// map. This doesn't splay.
+    pub fn successor<Q>(&self, key: &Q) -> Option<(&K, &V)> where K: Borrow<Q>, Q: Ord + ?Sized {
+        let compare = |other_key: &K, _: &V| {
+            if key < other_key.borrow() { Less } else { Greater }
+        };
+        lower_bound_with(compare, self.root_ref()).map(|(key, value)| (key, value))
+    }
+
+    /// Returns the pair with the largest key less than or equal to `key`. This doesn't splay.
+    pub fn floor<Q>(&self, key: &Q) -> Option<(&K, &V)> where K: Borrow<Q>, Q: Ord + ?Sized {
+        let compare = |other_key: &K, _: &V| key.cmp(other_key.borrow());
+        upper_bound_with(compare, self.root_ref()).map(|(key, value)| (key, value))
+    }
+
+    /// Returns the pair with the smallest key greater than or equal to `key`. This doesn't splay.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)> where K: Borrow<Q>, Q: Ord + ?Sized {
+        let compare = |other_key: &K, _: &V| key.cmp(other_key.borrow());
+        lower_bound_with(compare, self.root_ref()).map(|(key, value)| (key, value))
+    }
+
+    /// Inserts a batch of key-value pairs, like `extend`, but sorts them by key first and merges
+    /// them into the tree in a single pass. If the batch has duplicate keys, the pair that comes
+    /// last in the batch wins.
+    ///
+    /// Each key is splayed to the root of the subtree right of the previous one, rather than of
+    /// the whole tree, since all the keys still to come are in there. The pairs inserted so far
+    /// end up on the right spine of the tree, so if that leaves the tree degenerate, it is
+    /// rebalanced as by `rebalance`. If the map is empty, it's built as a balanced tree as in
+    /// `from_sorted_iter` instead.
+    pub fn bulk_insert(&mut self, mut items: Vec<(K, V)>) {
+        // The sort is stable, so duplicate keys stay in the order they were given.
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut deduplicated: Vec<(K, V)> = Vec::with_capacity(items.len());
+        for (key, value) in items {
+            match deduplicated.last_mut() {
+                Some(last) if last.0 == key => {
+                    *last = (key, value);
+                    continue
+                }
+                _ => {}
+            }
+            deduplicated.push((key, value));
+        }
+        if self.is_empty() {
+            self.swap_trees(&mut SplayMap::from_sorted_iter(deduplicated));
+            return
+        }
+
+        let (mut rest, mut spine_length) = (&mut self.root, 0);
+        for (key, value) in deduplicated {
+            let found = match *rest {
+                Some(ref mut node) => {
+                    splay_with_key(&key, node, &self.counters);
+                    key == node.key_value.0
+                }
+                None => false,
+            };
+            if found {
+                rest.as_mut().unwrap().key_value.1 = value;
+            } else {
+                insert_at_root_of(rest, key, value);
+                self.size += 1;
+            }
+            rest = &mut rest.as_mut().unwrap().right;
+            spine_length += 1;
+        }
+        // The sizes and heights of the nodes on the spine don't count what went in below them.
+        update_spine(&mut self.root, spine_length, true);
+        if self.is_degenerate() {
+            self.rebalance();
+        }
+        self.assert_consistent();
+    }
+
+    /// Swaps the trees of this map and `other`, leaving the settings and counters of both alone.
+    fn swap_trees(&mut self, other: &mut SplayMap<K, V>) {
+        mem::swap(&mut self.root, &mut other.root);
+        mem::swap(&mut self.size, &mut other.size);
+    }
+
+    /// Moves all the pairs of `other` into this map. Pairs whose keys aren't in this map yet are
+    /// inserted, while for the others, `combine` is called with the key, the existing value, and
+    /// the incoming value to fold them together. Each key is splayed once, by `try_insert`.
+    pub fn merge_with<F>(&mut self, other: SplayMap<K, V>, mut combine: F)
+                         where F: FnMut(&K, &mut V, V) {
+        for (key, value) in other.into_iter() {
+            if let Err(error) = self.try_insert(key, value) {
+                combine(&error.key, error.existing, error.value);
+            }
+        }
+    }
+
+    /// Moves all the pairs of `other` into this map, leaving `other` empty. Where both maps have a
+    /// key, the value from `other` wins.
+    ///
+    /// If all the keys of one map are less than all the keys of the other, the trees are joined by
+    /// splaying the largest or smallest key of this map to the root and hanging the tree of
+    /// `other` off of it, which takes a single splay. Otherwise, the pairs of `other` are inserted
+    /// one by one.
+    pub fn append(&mut self, other: &mut SplayMap<K, V>) {
+        if other.is_empty() {
+            return
+        }
+        if self.is_empty() {
+            self.swap_trees(other);
+            return
+        }
+        let after = last_pair(&self.root).unwrap().0 < first_pair(&other.root).unwrap().0;
+        let before = last_pair(&other.root).unwrap().0 < first_pair(&self.root).unwrap().0;
+        if !after && !before {
+            self.extend(other.drain());
+            return
+        }
+
+        let other_root = other.root_mut().take();
+        let root = self.root.as_mut().unwrap();
+        if after {
+            splay_with(|_, _| Greater, root, &self.counters);
+            root.right = other_root;
+        } else {
+            splay_with(|_, _| Less, root, &self.counters);
+            root.left = other_root;
+        }
+        root.update();
+        self.size += mem::replace(&mut other.size, 0);
+        self.assert_consistent();
+    }
+
+    /// Removes the pairs with keys greater than or equal to `key` from this map, and returns them
+    /// in a new map. After splaying `key`, the boundary lies at the root, so the tree is cut in
+    /// two there, and the sizes of the halves come from the subtree sizes.
+    pub fn split_off<Q>(&mut self, key: &Q) -> SplayMap<K, V> where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut root = match self.root_mut().take() {
+            Some(root) => root,
+            None => {
+                let mut split = SplayMap::new();
+                split.splay_on_read = self.splay_on_read;
+                return split
+            }
+        };
+        splay_with_key(key, &mut root, &self.counters);
+        let (left, right) = if root.key_value.0.borrow() >= key {
+            let left = root.pop_left();
+            root.update();
+            (left, Some(root))
+        } else {
+            let right = root.pop_right();
+            root.update();
+            (Some(root), right)
+        };
+        let size = subtree_size(&left);
+        let mut split = SplayMap {
+            root: right,
+            size: self.size - size,
+            counters: Counters::default(),
+            splay_on_read: self.splay_on_read,
+        };
+        *self.root_mut() = left;
+        self.size = size;
+        self.assert_consistent();
+        split.assert_consistent();
+        split
+    }
+
+    /// Returns the number of keys in the given range without visiting them. This walks down the
+    /// tree twice using the subtree sizes in the nodes, without splaying, so it takes time
+    /// proportional to the depth of the tree. A range whose start is after its end is empty.
+    pub fn count_range<Q, R>(&self, range: R) -> usize
+                             where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let start = match range.start_bound() {
+            Bound::Included(key) => self.count_before(key, false),
+            Bound::Excluded(key) => self.count_before(key, true),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => self.count_before(key, true),
+            Bound::Excluded(key) => self.count_before(key, false),
+            Bound::Unbounded => self.size,
+        };
+        end.saturating_sub(start)
+    }
+
+    /// Returns the pair with the `index`th smallest key, counting from zero, or `None` if `index`
+    /// is out of bounds. This walks down the tree using the subtree sizes without splaying.
+    pub fn nth(&self, mut index: usize) -> Option<(&K, &V)> {
+        let mut cur = self.root_ref().as_ref();
+        while let Some(node) = cur {
+            let left_size = subtree_size(&node.left);
+            match index.cmp(&left_size) {
+                Less => cur = node.left.as_ref(),
+                Equal => return Some((&node.key_value.0, &node.key_value.1)),
+                Greater => {
+                    index -= left_size + 1;
+                    cur = node.right.as_ref();
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of keys less than `key`, which is the index of `key` in the map if it's
+    /// present. Like `nth`, this doesn't splay.
+    pub fn rank<Q>(&self, key: &Q) -> usize where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.count_before(key, false)
+    }
+
+    /// Returns the number of keys less than `key`, or less than or equal to it if `inclusive` is
+    /// true.
+    fn count_before<Q>(&self, key: &Q, inclusive: bool) -> usize
+                       where K: Borrow<Q>, Q: Ord + ?Sized {
+        let (mut count, mut cur) = (0, self.root_ref().as_ref());
+        while let Some(node) = cur {
+            match key.cmp(node.key_value.0.borrow()) {
+                Less => cur = node.left.as_ref(),
+                Equal if !inclusive => cur = node.left.as_ref(),
+                Equal | Greater => {
+                    count += subtree_size(&node.left) + 1;
+                    cur = node.right.as_ref();
+                }
+            }
+        }
+        count
+    }
+
+    /// Insert a key-value pair from the map. If the key already had a value
+    /// present in the map, that value is returned. Otherwise None is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(ref mut root) = self.root {
+            splay_with_key(&key, root, &self.counters);
+            if key == root.key_value.0 {
+                let old = mem::replace(&mut root.key_value.1, value);
+                return Some(old);
+            }
+        }
+        self.insert_at_root(key, value);
+        None
+    }
+
+    /// Inserts a key-value pair if the key isn't already in the map, returning a reference to
+    /// the new value. Otherwise, the map is left alone and the pair is handed back in an error
+    /// along with a reference to the existing value. Finding out whether the key is present and
+    /// inserting it take a single splay.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<&mut V, OccupiedError<'_, K, V>> {
+        let occupied = match self.root {
+            Some(ref mut root) => {
+                splay_with_key(&key, root, &self.counters);
+                key == root.key_value.0
+            }
+            None => false,
+        };
+        if occupied {
+            let root = self.root_mut().as_mut().unwrap();
+            return Err(OccupiedError { key, value, existing: &mut root.key_value.1 })
+        }
+        self.insert_at_root(key, value);
+        Ok(&mut self.root_mut().as_mut().unwrap().key_value.1)
+    }
+
+    /// Returns a mutable reference to the value for the given key, inserting `V::default()` first
+    /// if the key isn't in the map. This is shorthand for `entry(key).or_default()`.
+    pub fn get_mut_or_default(&mut self, key: K) -> &mut V where V: Default {
+        self.entry(key).or_default()
+    }
+
+    /// Returns a mutable reference to the value for the given key, inserting the result of `f`
+    /// first if the key isn't in the map. `f` is only called if the key is absent. This is
+    /// shorthand for `entry(key).or_insert_with(f)`.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &mut V where F: FnOnce() -> V {
+        self.entry(key).or_insert_with(f)
+    }
+
+    /// Inserts a key that isn't in the map yet as the new root, once the tree has been splayed
+    /// around it.
+    fn insert_at_root(&mut self, key: K, value: V) {
+        insert_at_root_of(self.root_mut(), key, value);
+        self.size += 1;
+        self.assert_consistent();
+    }
+
+    /// Inserts a key-value pair like `insert`, but if the map already has a key equal to `key`,
+    /// replaces that key as well as its value and returns the old pair. This matters for keys
+    /// that compare equal without being identical.
+    pub fn replace(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some(ref mut root) = self.root {
+            splay_with_key(&key, root, &self.counters);
+            if key == root.key_value.0 {
+                return Some(mem::replace(&mut root.key_value, (key, value)))
+            }
+        }
+        self.insert_at_root(key, value);
+        None
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>, Q: Ord + ?Sized
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes a key from the map, returning the stored key and its value if the key was
+    /// previously in the map.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+        where K: Borrow<Q>, Q: Ord + ?Sized
+    {
+        match self.root {
             None => { return None; }
             Some(ref mut root) => {
-                splay_with_key(key, root);
+                splay_with_key(key, root, &self.counters);
                 if key != root.key_value.0.borrow() { return None }
             }
         }
 
+        Some(self.remove_root())
+    }
+
+    /// Removes the pair at the root of the tree, which must not be empty, joining its subtrees
+    /// by splaying the largest key of the left one up to take its place.
+    fn remove_root(&mut self) -> (K, V) {
         // TODO: Extra storage of None isn't necessary
-        let (value, left, right) = match *self.root_mut().take().unwrap() {
-            Node {key_value: (_, value), left, right} => (value, left, right)
-        };
+        let Node { key_value, left, right, .. } = *self.root_mut().take().unwrap();
 
         *self.root_mut() = match left {
             None => right,
             Some(mut node) => {
-                splay_with_key(key, &mut node);
+                splay_with(|_, _| Greater, &mut node, &self.counters);
                 node.right = right;
+                node.update();
                 Some(node)
             }
         };
 
         self.size -= 1;
-        return Some(value);
+        self.assert_consistent();
+        key_value
+    }
+}
+
+impl<K: Ord, V> SplayMap<K, V> {
+    /// Returns the entry for the given key, which may or may not be in the map. The key, or the
+    /// closest one to it, is splayed to the root of the tree, so inserting a value through a
+    /// vacant entry or changing one through an occupied entry doesn't splay again.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let occupied = match self.root {
+            Some(ref mut root) => {
+                splay_with_key(&key, root, &self.counters);
+                key == root.key_value.0
+            }
+            None => false,
+        };
+        if occupied {
+            Entry::Occupied(OccupiedEntry { map: self })
+        } else {
+            Entry::Vacant(VacantEntry { map: self, key })
+        }
+    }
+
+    /// Returns the entry with the smallest key, or `None` if the map is empty. The entry is
+    /// splayed to the root of the tree.
+    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>> {
+        splay_with(|_, _| Less, self.root.as_mut()?, &self.counters);
+        Some(OccupiedEntry { map: self })
+    }
+
+    /// Returns the entry with the largest key, or `None` if the map is empty. The entry is
+    /// splayed to the root of the tree.
+    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>> {
+        splay_with(|_, _| Greater, self.root.as_mut()?, &self.counters);
+        Some(OccupiedEntry { map: self })
+    }
+
+    /// Removes and returns the pair with the smallest key, or `None` if the map is empty.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.first_entry().map(OccupiedEntry::remove_entry)
+    }
+
+    /// Removes and returns the pair with the largest key, or `None` if the map is empty.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.last_entry().map(OccupiedEntry::remove_entry)
+    }
+}
+
+/// A place in a `SplayMap` for a key, as returned by `SplayMap::entry`.
+pub enum Entry<'a, K: Ord + 'a, V: 'a> {
+    /// The key is in the map.
+    Occupied(OccupiedEntry<'a, K, V>),
+    /// The key isn't in the map.
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key(),
+        }
+    }
+
+    /// Inserts `default` if the entry is vacant, and returns the value of the entry mutably.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the result of `default` if the entry is vacant, and returns the value of the entry
+    /// mutably.
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V where F: FnOnce() -> V {
+        self.or_insert_with_key(|_| default())
+    }
+
+    /// Inserts the result of calling `default` on the key if the entry is vacant, and returns the
+    /// value of the entry mutably.
+    pub fn or_insert_with_key<F>(self, default: F) -> &'a mut V where F: FnOnce(&K) -> V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Inserts `V::default()` if the entry is vacant, and returns the value of the entry mutably.
+    pub fn or_default(self) -> &'a mut V where V: Default {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value of the entry if it's occupied.
+    pub fn and_modify<F>(mut self, f: F) -> Entry<'a, K, V> where F: FnOnce(&mut V) {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// A key that isn't in a `SplayMap`, where the tree has been splayed around it so that it can be
+/// inserted as the new root.
+pub struct VacantEntry<'a, K: Ord + 'a, V: 'a> {
+    map: &'a mut SplayMap<K, V>,
+    key: K,
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    /// Returns the key that would be inserted.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Gives back the key without inserting it.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the key with the given value, and returns the value mutably.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.insert_at_root(self.key, value);
+        &mut self.map.root_mut().as_mut().unwrap().key_value.1
+    }
+}
+
+/// A pair in a `SplayMap` that can be inspected, changed, or removed in place. Like `CursorMut`,
+/// this keeps its pair splayed to the root of the tree.
+pub struct OccupiedEntry<'a, K: Ord + 'a, V: 'a> {
+    map: &'a mut SplayMap<K, V>,
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    /// Returns the key of the entry.
+    pub fn key(&self) -> &K {
+        &self.root().key_value.0
+    }
+
+    /// Returns the value of the entry.
+    pub fn get(&self) -> &V {
+        &self.root().key_value.1
+    }
+
+    /// Returns the value of the entry mutably.
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.root_mut().as_mut().unwrap().key_value.1
+    }
+
+    /// Returns the value of the entry mutably, for as long as the map was borrowed.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.root_mut().as_mut().unwrap().key_value.1
+    }
+
+    /// Replaces the value of the entry, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Removes the entry from the map, returning its value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the map, returning its key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_root()
+    }
+
+    fn root(&self) -> &Node<K, V> {
+        self.map.root_ref().as_ref().unwrap()
+    }
+}
+
+impl<K: Ord, V> SplayMap<K, V> {
+    /// Returns a cursor at the given key, or at the ghost position if the key isn't in the map.
+    pub fn cursor_at<Q>(&mut self, key: &Q) -> CursorMut<'_, K, V>
+                        where K: Borrow<Q>, Q: Ord + ?Sized {
+        let at_element = match self.root {
+            Some(ref mut root) => {
+                splay_with_key(key, root, &self.counters);
+                key == root.key_value.0.borrow()
+            }
+            None => false,
+        };
+        CursorMut { map: self, at_element }
+    }
+
+    /// Returns a cursor at the first pair of the map, or at the ghost position if it's empty.
+    pub fn cursor_front(&mut self) -> CursorMut<'_, K, V> {
+        let mut cursor = CursorMut { map: self, at_element: false };
+        cursor.move_next();
+        cursor
+    }
+
+    /// Returns a cursor at the last pair of the map, or at the ghost position if it's empty.
+    pub fn cursor_back(&mut self) -> CursorMut<'_, K, V> {
+        let mut cursor = CursorMut { map: self, at_element: false };
+        cursor.move_prev();
+        cursor
+    }
+}
+
+/// A cursor over a `SplayMap` that can move back and forth and insert and remove pairs where it
+/// is, modeled on the cursors of the standard linked list.
+///
+/// The cursor is either at a pair or at the "ghost" position, which lies past the last pair and
+/// before the first one.
+///
+/// Unlike `BTreeMap`'s cursors, this doesn't keep a stack of the path to its pair. It keeps its
+/// pair splayed to the root of the tree instead, so `move_next` and `move_prev` splay the
+/// neighbouring pair up to the root. A single move takes O(log n) amortized time and can take
+/// O(n) time in the worst case, and every move rotates the tree, so the map has a different shape
+/// afterwards even if nothing was inserted or removed. Stepping through a run of k pairs in order
+/// does take O(k + log n) amortized time in total, so a full walk over the map is O(n).
+pub struct CursorMut<'a, K: Ord + 'a, V: 'a> {
+    map: &'a mut SplayMap<K, V>,
+    at_element: bool,
+}
+
+impl<'a, K: Ord, V> CursorMut<'a, K, V> {
+    /// Returns the pair that the cursor is at, or `None` at the ghost position.
+    pub fn key_value(&mut self) -> Option<(&K, &mut V)> {
+        if !self.at_element {
+            return None
+        }
+        self.map.root_mut().as_mut().map(|root| {
+            let (ref key, ref mut value) = root.key_value;
+            (key, value)
+        })
+    }
+
+    /// Moves the cursor to the next pair. From the last pair, this moves to the ghost position,
+    /// and from the ghost position, this moves to the first pair. This splays the new pair to the
+    /// root, which takes O(log n) amortized time.
+    pub fn move_next(&mut self) {
+        let at_element = self.at_element;
+        self.at_element = match self.map.root {
+            Some(ref mut root) if at_element => splay_successor(root, &self.map.counters),
+            Some(ref mut root) => {
+                splay_with(|_, _| Less, root, &self.map.counters);
+                true
+            }
+            None => false,
+        };
+    }
+
+    /// Moves the cursor to the previous pair. From the first pair, this moves to the ghost
+    /// position, and from the ghost position, this moves to the last pair. This splays the new pair
+    /// to the root, which takes O(log n) amortized time.
+    pub fn move_prev(&mut self) {
+        let at_element = self.at_element;
+        self.at_element = match self.map.root {
+            Some(ref mut root) if at_element => splay_predecessor(root, &self.map.counters),
+            Some(ref mut root) => {
+                splay_with(|_, _| Greater, root, &self.map.counters);
+                true
+            }
+            None => false,
+        };
+    }
+
+    /// Inserts a pair right after the cursor, or at the front of the map from the ghost position.
+    /// The cursor stays where it is.
+    ///
+    /// Panics if the key doesn't belong between the pairs on either side of the new position.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        let at_element = self.at_element;
+        let root = self.map.root_mut();
+        match *root {
+            Some(ref mut root) if at_element => {
+                assert!(key > root.key_value.0 &&
+                            first_pair(&root.right).is_none_or(|next| key < next.0),
+                        "CursorMut::insert_after(): Key is out of order!");
+                let right = root.pop_right();
+                root.right = Some(Node::new(key, value, None, right));
+                root.update();
+            }
+            _ => {
+                assert!(first_pair(root).is_none_or(|first| key < first.0),
+                        "CursorMut::insert_after(): Key is out of order!");
+                let old_root = root.take();
+                *root = Some(Node::new(key, value, None, old_root));
+            }
+        }
+        self.map.size += 1;
+        self.map.assert_consistent();
+    }
+
+    /// Inserts a pair right before the cursor, or at the back of the map from the ghost position.
+    /// The cursor stays where it is.
+    ///
+    /// Panics if the key doesn't belong between the pairs on either side of the new position.
+    pub fn insert_before(&mut self, key: K, value: V) {
+        let at_element = self.at_element;
+        let root = self.map.root_mut();
+        match *root {
+            Some(ref mut root) if at_element => {
+                assert!(key < root.key_value.0 &&
+                            last_pair(&root.left).is_none_or(|prev| key > prev.0),
+                        "CursorMut::insert_before(): Key is out of order!");
+                let left = root.pop_left();
+                root.left = Some(Node::new(key, value, left, None));
+                root.update();
+            }
+            _ => {
+                assert!(last_pair(root).is_none_or(|last| key > last.0),
+                        "CursorMut::insert_before(): Key is out of order!");
+                let old_root = root.take();
+                *root = Some(Node::new(key, value, old_root, None));
+            }
+        }
+        self.map.size += 1;
+        self.map.assert_consistent();
+    }
+
+    /// Removes the pair at the cursor and moves the cursor to the next pair, or to the ghost
+    /// position if it was the last one. Returns `None` at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if !self.at_element {
+            return None
+        }
+        let Node { key_value, left, right, .. } = *self.map.root_mut().take().unwrap();
+        *self.map.root_mut() = match right {
+            None => {
+                self.at_element = false;
+                left
+            }
+            Some(mut right) => {
+                splay_with(|_, _| Less, &mut right, &self.map.counters);
+                right.left = left;
+                right.update();
+                Some(right)
+            }
+        };
+        self.map.size -= 1;
+        self.map.assert_consistent();
+        Some(key_value)
     }
 }
 
 impl<K: Ord, V> SplayMap<K, V> {
-    // These two functions provide safe access to the root node, and they should
-    // be valid to call in virtually all contexts.
+    // The tree is only ever restructured through a unique borrow of the map, so these are just
+    // shorthand for the field.
     fn root_mut(&mut self) -> &mut Option<Box<Node<K, V>>> {
-        unsafe { &mut *self.root.get() }
+        &mut self.root
     }
     fn root_ref(&self) -> &Option<Box<Node<K, V>>> {
-        unsafe { &*self.root.get() }
+        &self.root
     }
 }
 
@@ -313,7 +1872,7 @@ impl<'a, K: Ord, V, Q: ?Sized> Index<&'a Q> for SplayMap<K, V>
 {
     type Output = V;
     fn index(&self, index: &'a Q) -> &V {
-        self.get(index).expect("key not present in SplayMap")
+        self.get_no_splay(index).expect("key not present in SplayMap")
     }
 }
 
@@ -329,6 +1888,38 @@ impl<K: Ord, V> Default for SplayMap<K, V> {
     fn default() -> SplayMap<K, V> { SplayMap::new() }
 }
 
+impl<K: Ord + Debug, V: Debug> Debug for SplayMap<K, V> {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        formatter.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V: PartialEq> PartialEq for SplayMap<K, V> {
+    fn eq(&self, other: &SplayMap<K, V>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Ord, V: Eq> Eq for SplayMap<K, V> {}
+
+impl<K: Ord, V> IntoIterator for SplayMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> IntoIter<K, V> { SplayMap::into_iter(self) }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a SplayMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a mut SplayMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+    fn into_iter(self) -> IterMut<'a, K, V> { self.iter_mut() }
+}
+
 impl<K: Ord, V> FromIterator<(K, V)> for SplayMap<K, V> {
     fn from_iter<I: IntoIterator<Item=(K, V)>>(iterator: I) -> SplayMap<K, V> {
         let mut map = SplayMap::new();
@@ -345,53 +1936,114 @@ impl<K: Ord, V> Extend<(K, V)> for SplayMap<K, V> {
     }
 }
 
-impl<K, V> Iterator for IntoIter<K, V> {
-    type Item = (K, V);
-    fn next(&mut self) -> Option<(K, V)> {
-        let mut cur = match self.cur.take() {
-            Some(cur) => cur,
-            None => return None,
-        };
+/// Sorts the pairs and builds a balanced tree out of them, as `bulk_insert` does for an empty
+/// map. If there are duplicate keys, the pair that comes last in the vector wins.
+impl<K: Ord, V> From<Vec<(K, V)>> for SplayMap<K, V> {
+    fn from(pairs: Vec<(K, V)>) -> SplayMap<K, V> {
+        let mut map = SplayMap::new();
+        map.bulk_insert(pairs);
+        map
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Ord, V> From<BTreeMap<K, V>> for SplayMap<K, V> {
+    fn from(map: BTreeMap<K, V>) -> SplayMap<K, V> {
+        SplayMap::from_sorted_iter(map)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Ord, V> From<SplayMap<K, V>> for BTreeMap<K, V> {
+    fn from(map: SplayMap<K, V>) -> BTreeMap<K, V> {
+        map.into_iter().collect()
+    }
+}
+
+impl<K, V> IntoIter<K, V> {
+    /// Detaches the node with the smallest remaining key from the tree and returns it, with no
+    /// children.
+    fn next_node(&mut self) -> Option<Box<Node<K, V>>> {
+        let mut cur = self.cur.take()?;
         loop {
             match cur.pop_left() {
                 Some(node) => {
                     let mut node = node;
                     cur.left = node.pop_right();
+                    cur.update();
                     node.right = Some(cur);
+                    node.update();
                     cur = node;
                 }
 
                 None => {
                     self.cur = cur.pop_right();
                     // left and right fields are both None
-                    let node = *cur;
-                    let Node { key_value, .. } = node;
                     self.remaining -= 1;
-                    return Some(key_value);
+                    return Some(cur);
                 }
             }
         }
     }
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        let Node { key_value, .. } = *self.next_node()?;
+        Some(key_value)
+    }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
         (self.remaining, Some(self.remaining))
     }
+
+    /// Skips `n` pairs using the subtree sizes stored in the nodes. Whole subtrees that lie
+    /// before the `n`th pair are dropped without being rotated, so only the nodes on the path to
+    /// the `n`th pair are restructured. The skipped pairs still have to be freed, though.
+    fn nth(&mut self, mut n: usize) -> Option<(K, V)> {
+        while n > 0 {
+            let mut cur = self.cur.take()?;
+            let left_size = subtree_size(&cur.left);
+            if n > left_size {
+                // Skip the left subtree and this node.
+                destroy(cur.pop_left());
+                self.cur = cur.pop_right();
+                self.remaining -= left_size + 1;
+                n -= left_size + 1;
+            } else if n == left_size {
+                destroy(cur.pop_left());
+                cur.update();
+                self.cur = Some(cur);
+                self.remaining -= left_size;
+                n = 0;
+            } else {
+                // The `n`th pair is in the left subtree, so rotate it up and look again.
+                let mut node = cur.take_left();
+                cur.left = node.pop_right();
+                cur.update();
+                node.right = Some(cur);
+                node.update();
+                self.cur = Some(node);
+            }
+        }
+        self.next()
+    }
 }
 
 impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
     // Pretty much the same as the above code, but with left replaced with right
     // and vice-versa.
     fn next_back(&mut self) -> Option<(K, V)> {
-        let mut cur = match self.cur.take() {
-            Some(cur) => cur,
-            None => return None,
-        };
+        let mut cur = self.cur.take()?;
         loop {
             match cur.pop_right() {
                 Some(node) => {
                     let mut node = node;
                     cur.right = node.pop_left();
+                    cur.update();
                     node.left = Some(cur);
+                    node.update();
                     cur = node;
                 }
 
@@ -406,16 +2058,286 @@ impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
             }
         }
     }
+
+    /// Skips `n` pairs from the back, like `nth`.
+    fn nth_back(&mut self, mut n: usize) -> Option<(K, V)> {
+        while n > 0 {
+            let mut cur = self.cur.take()?;
+            let right_size = subtree_size(&cur.right);
+            if n > right_size {
+                destroy(cur.pop_right());
+                self.cur = cur.pop_left();
+                self.remaining -= right_size + 1;
+                n -= right_size + 1;
+            } else if n == right_size {
+                destroy(cur.pop_right());
+                cur.update();
+                self.cur = Some(cur);
+                self.remaining -= right_size;
+                n = 0;
+            } else {
+                let mut node = cur.take_right();
+                cur.right = node.pop_left();
+                cur.update();
+                node.left = Some(cur);
+                node.update();
+                self.cur = Some(node);
+            }
+        }
+        self.next_back()
+    }
 }
 
 impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
 
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+    fn next(&mut self) -> Option<K> { self.iter.next().map(|(key, _)| key) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<K, V> DoubleEndedIterator for IntoKeys<K, V> {
+    fn next_back(&mut self) -> Option<K> { self.iter.next_back().map(|(key, _)| key) }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+    fn next(&mut self) -> Option<V> { self.iter.next().map(|(_, value)| value) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<K, V> DoubleEndedIterator for IntoValues<K, V> {
+    fn next_back(&mut self) -> Option<V> { self.iter.next_back().map(|(_, value)| value) }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+impl<K, V> Iterator for Drain<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> { self.iter.next() }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<K, V> DoubleEndedIterator for Drain<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> { self.iter.next_back() }
+}
+
+impl<K, V> ExactSizeIterator for Drain<K, V> {}
+
+impl<K, V> Drop for Drain<K, V> {
+    fn drop(&mut self) {
+        // Like `SplayMap::clear`, free the rest of the nodes without recursing.
+        for _ in self {}
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.remaining == 0 {
+            return None
+        }
+        let node = self.stack.pop()?;
+        let mut cur = node.right.as_deref();
+        while let Some(next) = cur {
+            self.stack.push(next);
+            cur = next.left.as_deref();
+        }
+        self.remaining -= 1;
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.remaining == 0 {
+            return None
+        }
+        let node = self.back_stack.pop()?;
+        let mut cur = node.left.as_deref();
+        while let Some(next) = cur {
+            self.back_stack.push(next);
+            cur = next.right.as_deref();
+        }
+        self.remaining -= 1;
+        Some((&node.key_value.0, &node.key_value.1))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> IterMut<'a, K, V> {
+    /// Pushes the pairs on the left spine of the given subtree onto the stack.
+    fn push_left_spine(&mut self, mut cur: Option<&'a mut Node<K, V>>) {
+        while let Some(node) = cur {
+            let Node { key_value: (ref key, ref mut value), ref mut left, ref mut right, .. } =
+                *node;
+            self.stack.push((key, value, right.as_deref_mut()));
+            cur = left.as_deref_mut();
+        }
+    }
+
+    /// Pushes the pairs on the path down the given subtree to the first key that lies after the
+    /// given start bound, and whose keys lie after it, onto the stack, like
+    /// `SplayMap::iter_within`.
+    fn push_after_start<Q>(&mut self, mut cur: Option<&'a mut Node<K, V>>, start: Bound<&Q>)
+                           where K: Borrow<Q>, Q: Ord + ?Sized {
+        while let Some(node) = cur {
+            let Node { key_value: (ref key, ref mut value), ref mut left, ref mut right, .. } =
+                *node;
+            if is_after_start(key.borrow(), start) {
+                self.stack.push((key, value, right.as_deref_mut()));
+                cur = left.as_deref_mut();
+            } else {
+                cur = right.as_deref_mut();
+            }
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        if self.remaining == 0 {
+            return None
+        }
+        let (key, value, right) = self.stack.pop()?;
+        self.push_left_spine(right);
+        self.remaining -= 1;
+        Some((key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Range<'a, K, V> {}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for RangeMut<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> {
+        self.iter.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.iter.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<&'a V> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.iter.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
 impl<K: Clone + Ord, V: Clone> Clone for SplayMap<K, V> {
     fn clone(&self) -> SplayMap<K, V> {
         SplayMap {
-            root: UnsafeCell::new(self.root_ref().clone()),
+            root: clone_tree(self.root_ref()),
             size: self.size,
+            counters: Counters::default(),
+            splay_on_read: self.splay_on_read,
+        }
+    }
+
+    /// Overwrites this map with a copy of `source`, reusing the nodes of this map. The nodes are
+    /// paired up in key order, so where the two maps hold the same keys, each key and value is
+    /// overwritten with `clone_from` in place. Only as many nodes as the sizes of the maps differ
+    /// by are allocated or freed. The copy is built as a balanced tree rather than in the shape
+    /// of `source`, so splaying `source` doesn't keep the nodes from lining up.
+    fn clone_from(&mut self, source: &SplayMap<K, V>) {
+        let mut spare = IntoIter { cur: self.root_mut().take(), remaining: self.size };
+        {
+            let mut nodes = source.iter().map(|(key, value)| {
+                match spare.next_node() {
+                    Some(mut node) => {
+                        node.key_value.0.clone_from(key);
+                        node.key_value.1.clone_from(value);
+                        node
+                    }
+                    None => Node::new(key.clone(), value.clone(), None, None),
+                }
+            });
+            *self.root_mut() = build_balanced(&mut nodes, source.size);
         }
+        // Free the leftover nodes without recursing.
+        for _ in spare {}
+        self.size = source.size;
+        self.splay_on_read = source.splay_on_read;
+        self.assert_consistent();
     }
 }
 
@@ -8,24 +8,56 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-#[derive(Clone)]
+use alloc::boxed::Box;
+use core::cmp;
+
 pub struct Node<K, V> {
     pub key_value: (K, V),
     pub left: Option<Box<Node<K, V>>>,
     pub right: Option<Box<Node<K, V>>>,
+    /// The number of nodes in the subtree rooted at this node, including this one.
+    pub size: usize,
+    /// The number of nodes on the longest path from this node down to a leaf, including this one.
+    pub height: u32,
+}
+
+/// Returns the number of nodes in the given subtree.
+pub fn subtree_size<K, V>(node: &Option<Box<Node<K, V>>>) -> usize {
+    match *node {
+        Some(ref node) => node.size,
+        None => 0,
+    }
+}
+
+/// Returns the height of the given subtree, which is zero if it's empty.
+pub fn subtree_height<K, V>(node: &Option<Box<Node<K, V>>>) -> u32 {
+    match *node {
+        Some(ref node) => node.height,
+        None => 0,
+    }
 }
 
 impl<K, V> Node<K, V> {
     pub fn new(k: K, v: V,
                l: Option<Box<Node<K, V>>>,
                r: Option<Box<Node<K, V>>>) -> Box<Node<K, V>> {
+        let size = 1 + subtree_size(&l) + subtree_size(&r);
+        let height = 1 + cmp::max(subtree_height(&l), subtree_height(&r));
         Box::new(Node {
             key_value: (k, v),
             left: l,
             right: r,
+            size,
+            height,
         })
     }
 
+    /// Recomputes the size and height of this subtree from those of its children.
+    pub fn update(&mut self) {
+        self.size = 1 + subtree_size(&self.left) + subtree_size(&self.right);
+        self.height = 1 + cmp::max(subtree_height(&self.left), subtree_height(&self.right));
+    }
+
     pub fn pop_left(&mut self) -> Option<Box<Node<K, V>>> {
         self.left.take()
     }
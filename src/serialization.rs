@@ -0,0 +1,57 @@
+// Copyright 2016 The Servo Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `serde` implementations for `SplayMap`, enabled by the `serde` feature.
+//!
+//! A map is serialized as a serde map in key order, and deserialized by inserting each pair into a
+//! fresh map, so the tree shape isn't preserved.
+
+use core::marker::PhantomData;
+use map::SplayMap;
+use serde::de::{Deserialize, Deserializer, Error, MapVisitor, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+impl<K, V> Serialize for SplayMap<K, V> where K: Ord + Serialize, V: Serialize {
+    fn serialize<S>(&self, serializer: &mut S) -> Result<(), S::Error> where S: Serializer {
+        let mut state = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            serializer.serialize_map_key(&mut state, key)?;
+            serializer.serialize_map_value(&mut state, value)?;
+        }
+        serializer.serialize_map_end(state)
+    }
+}
+
+impl<K, V> Deserialize for SplayMap<K, V> where K: Ord + Deserialize, V: Deserialize {
+    fn deserialize<D>(deserializer: &mut D) -> Result<SplayMap<K, V>, D::Error>
+                      where D: Deserializer {
+        deserializer.deserialize_map(SplayMapVisitor(PhantomData))
+    }
+}
+
+struct SplayMapVisitor<K, V>(PhantomData<(K, V)>);
+
+impl<K, V> Visitor for SplayMapVisitor<K, V> where K: Ord + Deserialize, V: Deserialize {
+    type Value = SplayMap<K, V>;
+
+    fn visit_unit<E>(&mut self) -> Result<SplayMap<K, V>, E> where E: Error {
+        Ok(SplayMap::new())
+    }
+
+    fn visit_map<M>(&mut self, mut visitor: M) -> Result<SplayMap<K, V>, M::Error>
+                    where M: MapVisitor {
+        let mut map = SplayMap::new();
+        while let Some((key, value)) = visitor.visit()? {
+            map.insert(key, value);
+        }
+        visitor.end()?;
+        Ok(map)
+    }
+}
@@ -2,10 +2,18 @@
 // http://creativecommons.org/publicdomain/zero/1.0/
 
 use app_units::Au;
-use exclusions::{Exclusions, Point, Side, Size};
+use au::{self, AuExt};
+use exclusions::{Band, BandsError, ClearSide, Direction, Exclusions, Margins, PlacementError,
+                 Point, Rectangle, Side, Size};
+use map::{Entry, SplayMap, TreeStats};
 use quickcheck::{Arbitrary, Gen};
 use std::cmp;
-use std::i32;
+use std::mem;
+use std::ops::Bound;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+use std::string::ToString;
+use std::vec::Vec;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub struct InlineSize(Au);
@@ -34,25 +42,6 @@ impl Arbitrary for Exclusion {
     }
 }
 
-impl Arbitrary for Size {
-    fn arbitrary<G: Gen>(gen: &mut G) -> Size {
-        Size {
-            inline: Au(Arbitrary::arbitrary(gen)),
-            block: Au(Arbitrary::arbitrary(gen)),
-        }
-    }
-}
-
-impl Arbitrary for Side {
-    fn arbitrary<G: Gen>(gen: &mut G) -> Side {
-        if Arbitrary::arbitrary(gen) {
-            Side::Left
-        } else {
-            Side::Right
-        }
-    }
-}
-
 #[derive(Clone, Debug)]
 pub struct ExcludedArea {
     exclusion: Exclusion,
@@ -177,5 +166,1961 @@ quickcheck! {
         }
         true
     }
+
+    fn check_clone_from(source: Vec<(u32, u32)>, dest: Vec<(u32, u32)>) -> bool {
+        let source: SplayMap<u32, u32> = source.into_iter().collect();
+        let mut dest: SplayMap<u32, u32> = dest.into_iter().collect();
+        dest.clone_from(&source);
+        dest.check_invariants();
+        dest.into_iter().eq(source.into_iter())
+    }
+
+    fn check_into_iter_nth(keys: Vec<u32>, n: usize, m: usize) -> bool {
+        let map: SplayMap<u32, ()> = keys.into_iter().map(|key| (key, ())).collect();
+        let naive: Vec<u32> = map.clone().into_iter().map(|(key, _)| key).collect();
+        let mut naive = naive.into_iter();
+        let mut iter = map.into_iter();
+        iter.nth(n).map(|(key, _)| key) == naive.nth(n) &&
+            iter.nth_back(m).map(|(key, _)| key) == naive.nth_back(m) &&
+            iter.len() == naive.len() && iter.map(|(key, _)| key).eq(naive)
+    }
+
+    fn check_subtree_sizes_after_lookups(keys: Vec<u32>, lookups: Vec<u32>) -> bool {
+        let mut map: SplayMap<u32, ()> = keys.into_iter().map(|key| (key, ())).collect();
+        for key in lookups {
+            map.get(&key);
+            map.get_with_mut(|other_key, _| key.cmp(other_key));
+            map.check_invariants();
+        }
+        true
+    }
+
+    fn check_bulk_insert(existing: Vec<(u32, u32)>, items: Vec<(u32, u32)>) -> bool {
+        let mut expected: SplayMap<u32, u32> = existing.iter().cloned().collect();
+        let mut map = expected.clone();
+        expected.extend(items.iter().cloned());
+        map.bulk_insert(items);
+        map.check_invariants();
+        map.into_iter().eq(expected.into_iter())
+    }
+
+    #[cfg(feature = "std")]
+    fn check_btree_map_round_trip(pairs: Vec<(u32, u32)>) -> bool {
+        let btree_map: BTreeMap<u32, u32> = pairs.into_iter().collect();
+        let map = SplayMap::from(btree_map.clone());
+        BTreeMap::from(map) == btree_map
+    }
+}
+
+#[test]
+fn check_band_merging() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(20)));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(5)));
+    assert_eq!(exclusions.band_count(), 2);
+}
+
+#[test]
+fn check_clear() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(100), Au(20)));
+    exclusions.clear();
+    assert_eq!(exclusions.band_count(), 1);
+    let placement = exclusions.place(Side::Left, &Size::new(Au(100), Au(10)));
+    assert_eq!((placement.origin.inline, placement.origin.block), (Au(0), Au(0)));
+    assert_eq!(placement.available_inline_size, Au(100));
+}
+
+#[test]
+fn check_available_inline_size() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(5)));
+    assert_eq!(exclusions.available_inline_size(Au(0)), Au(50));
+    assert_eq!(exclusions.available_inline_size(Au(7)), Au(70));
+    assert_eq!(exclusions.available_inline_size(Au(10)), Au(100));
+}
+
+#[test]
+fn check_line_box() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(30)));
+    assert_eq!(exclusions.line_box(Au(0), Au(5)), (Au(30), Au(80)));
+    assert_eq!(exclusions.line_box(Au(15), Au(5)), (Au(10), Au(80)));
+    assert_eq!(exclusions.line_box(Au(15), Au(10)), (Au(10), Au(80)));
+    assert_eq!(exclusions.line_box(Au(5), Au(30)), (Au(30), Au(80)));
+    assert_eq!(exclusions.line_box(Au(25), Au(0)), (Au(0), Au(80)));
+    assert_eq!(exclusions.line_box(Au(30), Au(10)), (Au(0), Au(100)));
+}
+
+#[test]
+fn check_tall_float_placement() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(80), Au(10)));
+    let placement = exclusions.place(Side::Left, &Size::new(Au(50), Au(30)));
+    assert_eq!((placement.origin.inline, placement.origin.block), (Au(0), Au(10)));
+    assert_eq!(placement.available_inline_size, Au(100));
+    let placement = exclusions.place(Side::Right, &Size::new(Au(20), Au(30)));
+    assert_eq!((placement.origin.inline, placement.origin.block), (Au(80), Au(0)));
+    assert_eq!(placement.available_inline_size, Au(20));
+}
+
+#[test]
+fn check_clearance() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+    assert_eq!(exclusions.clearance(Au(0), ClearSide::Left), Au(10));
+    assert_eq!(exclusions.clearance(Au(0), ClearSide::Right), Au(20));
+    assert_eq!(exclusions.clearance(Au(0), ClearSide::Both), Au(20));
+    assert_eq!(exclusions.clearance(Au(15), ClearSide::Left), Au(15));
+    assert_eq!(exclusions.clearance(Au(15), ClearSide::Both), Au(20));
+    assert_eq!(exclusions.clearance(Au(25), ClearSide::Both), Au(25));
+}
+
+#[test]
+fn check_clone_independence() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    let mut snapshot = exclusions.clone();
+    snapshot.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+    snapshot.exclude(Side::Left, &Size::new(Au(50), Au(5)));
+    assert_eq!(exclusions.band_count(), 2);
+    assert_eq!(exclusions.line_box(Au(0), Au(20)), (Au(30), Au(100)));
+    assert_eq!(snapshot.line_box(Au(0), Au(20)), (Au(50), Au(80)));
+}
+
+#[test]
+fn check_try_place() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    let error = exclusions.try_place(Side::Left, &Size::new(Au(101), Au(10))).unwrap_err();
+    assert_eq!(error, PlacementError::TooWide { requested: Au(101), available: Au(100) });
+    let origin = exclusions.try_place(Side::Right, &Size::new(Au(100), Au(10))).unwrap();
+    assert_eq!((origin.inline, origin.block), (Au(0), Au(10)));
+
+    // Once the exclusions on both sides overlap all the way down, no band has any room left.
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au::MAX));
+    exclusions.exclude(Side::Right, &Size::new(Au(80), Au::MAX));
+    let error = exclusions.try_place(Side::Left, &Size::new(Au(1), Au(10))).unwrap_err();
+    assert_eq!(error, PlacementError::TooWide { requested: Au(1), available: Au(0) });
+}
+
+#[test]
+fn check_resize() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+    exclusions.resize(Au(200));
+    assert_eq!(exclusions.available_inline_size(Au(0)), Au(150));
+    assert_eq!(exclusions.available_inline_size(Au(10)), Au(180));
+    assert_eq!(exclusions.line_box(Au(0), Au(10)), (Au(30), Au(180)));
+    let origin = exclusions.try_place(Side::Right, &Size::new(Au(20), Au(10))).unwrap();
+    assert_eq!((origin.inline, origin.block), (Au(160), Au(0)));
+}
+
+#[test]
+fn check_bands() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+    let bands: Vec<_> = exclusions.bands().map(|(block_position, band)| {
+        (block_position, band.left(), band.right(), band.length(), band.available_size(Au(100)))
+    }).collect();
+    assert_eq!(bands, vec![
+        (Au(0), Au(-30), Au(-20), Au(10), Au(50)),
+        (Au(10), Au(0), Au(-20), Au(10), Au(80)),
+        (Au(20), Au(0), Au(0), Au(i32::MAX - 20), Au(100)),
+    ]);
+}
+
+#[test]
+fn check_has_exclusions() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert!(!exclusions.has_exclusions());
+    exclusions.exclude(Side::Left, &Size::new(Au(0), Au(10)));
+    assert!(!exclusions.has_exclusions());
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(10)));
+    assert!(exclusions.has_exclusions());
+    assert_eq!(exclusions.band_count(), 2);
+    exclusions.clear();
+    assert!(!exclusions.has_exclusions());
+    assert_eq!(exclusions.band_count(), 1);
+}
+
+#[test]
+fn check_checked_arithmetic() {
+    let max = Au(i32::MAX);
+    assert_eq!(max.checked_add(Au(1)), None);
+    assert_eq!(max.checked_add(Au(-1)), Some(Au(i32::MAX - 1)));
+    assert_eq!(Au(i32::MIN).checked_sub(Au(1)), None);
+    assert_eq!(Au(10).checked_sub(Au(30)), Some(Au(-20)));
+    assert_eq!(Au(i32::MIN).checked_neg(), None);
+    assert_eq!(max.checked_neg(), Some(Au(-i32::MAX)));
+}
+
+#[test]
+fn check_saturating_arithmetic() {
+    assert_eq!(Au(i32::MAX).saturating_add(Au(1)), Au(i32::MAX));
+    assert_eq!(Au(i32::MIN).saturating_add(Au(-1)), Au(i32::MIN));
+    assert_eq!(Au(i32::MIN).saturating_sub(Au(1)), Au(i32::MIN));
+    assert_eq!(Au(10).saturating_sub(Au(30)), Au(-20));
+    assert_eq!(Au(i32::MIN).saturating_neg(), Au(i32::MAX));
+    assert_eq!(Au(7).saturating_neg(), Au(-7));
+}
+
+#[test]
+fn check_exclusions_near_sentinel() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(i32::MAX)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(i32::MAX - 1)));
+    assert_eq!(exclusions.band_count(), 2);
+    assert_eq!(exclusions.available_inline_size(Au(i32::MAX - 2)), Au(50));
+    assert_eq!(exclusions.available_inline_size(Au(i32::MAX - 1)), Au(70));
+    assert_eq!(exclusions.line_box(Au(5), Au(i32::MAX)), (Au(30), Au(80)));
+    let placement = exclusions.place(Side::Left, &Size::new(Au(60), Au(10)));
+    assert_eq!(placement.origin.block, Au(i32::MAX - 1));
+    assert_eq!(placement.available_inline_size, Au(70));
+}
+
+#[test]
+fn check_sum() {
+    let lengths = vec![Au(10), Au(-4), Au(30)];
+    assert_eq!(au::sum(&lengths), Au(36));
+    assert_eq!(au::sum(lengths), Au(36));
+    assert_eq!(au::sum(Vec::<Au>::new()), Au(0));
+    assert_eq!(au::sum(vec![Au(i32::MAX - 5), Au(10), Au(-10)]), Au(i32::MAX - 10));
+
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    assert_eq!(au::sum(exclusions.bands().map(|(_, band)| band.length())), Au(i32::MAX));
+}
+
+#[test]
+fn check_abs_and_clamping() {
+    assert_eq!(Au(-7).abs(), Au(7));
+    assert_eq!(Au(7).abs(), Au(7));
+    assert_eq!(Au(i32::MIN).abs(), Au(i32::MAX));
+    assert_eq!(Au(-7).checked_abs(), Some(Au(7)));
+    assert_eq!(Au(i32::MIN).checked_abs(), None);
+    assert_eq!(Au(-7).max(Au(0)), Au(0));
+    assert_eq!(Au(-7).min(Au(0)), Au(-7));
+    assert_eq!(Au(120).clamp(Au(0), Au(100)), Au(100));
+    assert_eq!(Au(-20).clamp(Au(0), Au(100)), Au(0));
+}
+
+#[test]
+fn check_grid_arithmetic() {
+    assert_eq!(Au(130).rem_euclid(Au(60)), Au(10));
+    assert_eq!(Au(-130).rem_euclid(Au(60)), Au(50));
+    assert_eq!(Au(-130) % 60, Au(-10));
+    assert!(Au(-120).is_multiple_of(Au(60)));
+    assert!(Au(120).is_multiple_of(Au(-60)));
+    assert!(!Au(-130).is_multiple_of(Au(60)));
+    assert!(Au(0).is_multiple_of(Au(0)) && !Au(5).is_multiple_of(Au(0)));
+    assert!(Au(i32::MIN).is_multiple_of(Au(-1)));
+
+    assert_eq!(Au(89).round_to_nearest(Au(60)), Au(60));
+    assert_eq!(Au(90).round_to_nearest(Au(60)), Au(120));
+    assert_eq!(Au(-89).round_to_nearest(Au(60)), Au(-60));
+    assert_eq!(Au(-90).round_to_nearest(Au(60)), Au(-60));
+    assert_eq!(Au(-91).round_to_nearest(Au(60)), Au(-120));
+    assert_eq!(Au(119).round_down_to(Au(60)), Au(60));
+    assert_eq!(Au(-1).round_down_to(Au(60)), Au(-60));
+    assert_eq!(Au(-120).round_down_to(Au(60)), Au(-120));
+    assert_eq!(Au(i32::MAX).round_to_nearest(Au(60)), Au(i32::MAX - 7));
+    assert_eq!(Au(i32::MAX - 1).round_to_nearest(Au(i32::MAX)), Au(i32::MAX));
+    assert_eq!(Au(i32::MIN).round_down_to(Au(60)), Au(i32::MIN));
+}
+
+#[test]
+fn check_display() {
+    assert_eq!(Au(720).display().to_string(), "12px (720au)");
+    assert_eq!(Au(-750).display().to_string(), "-12.5px (-750au)");
+}
+
+quickcheck! {
+    fn check_px_round_trip(px: i16) -> bool {
+        let px = px as i32;
+        Au::from_px(px).to_nearest_px() == px && Au::from_px(px).to_px() == px &&
+            Au::from_f32_px(px as f32) == Au::from_px(px) &&
+            Au::from_f32_px(Au::from_px(px).to_f32_px()) == Au::from_px(px)
+    }
+
+    fn check_assign_operators(a: i32, b: i32) -> bool {
+        let (mut sum, mut difference) = (Au(a), Au(a));
+        sum += Au(b);
+        difference -= Au(b);
+        sum == Au(a) + Au(b) && difference == Au(a) - Au(b)
+    }
+
+    fn check_band_ends_do_not_overflow(inline_size: InlineSize, exclusions: Vec<Exclusion>)
+                                       -> bool {
+        let mut zone = Exclusions::new(inline_size.0);
+        for exclusion in exclusions {
+            zone.exclude(exclusion.side, &exclusion.size);
+        }
+        let band_ends: Vec<_> = zone.bands().map(|(block_position, band)| {
+            block_position.checked_add(band.length())
+        }).collect();
+        band_ends.iter().all(|band_end| band_end.is_some()) &&
+            band_ends.last() == Some(&Some(Au(i32::MAX)))
+    }
+}
+
+#[test]
+fn check_point_and_size_operators() {
+    let (origin, size) = (Point::new(Au(10), Au(20)), Size::new(Au(30), Au(5)));
+    let end = origin + size;
+    assert_eq!((end.inline, end.block), (Au(40), Au(25)));
+    let start = end - size;
+    assert_eq!((start.inline, start.block), (Au(10), Au(20)));
+    let difference = end - origin;
+    assert_eq!((difference.inline, difference.block), (Au(30), Au(5)));
+    let total = size + Size::new(Au(1), Au(2));
+    assert_eq!((total.inline, total.block), (Au(31), Au(7)));
+    assert_eq!(size.area(), Au(150));
+}
+
+#[test]
+fn check_sides() {
+    assert_eq!(Side::all(), [Side::Left, Side::Right]);
+    for &side in &Side::all() {
+        assert!(side.opposite() != side);
+        assert_eq!(side.opposite().opposite(), side);
+    }
+}
+
+#[test]
+fn check_right_to_left() {
+    let mut ltr = Exclusions::new(Au(100));
+    let mut rtl = Exclusions::with_direction(Au(100), Direction::Rtl);
+    ltr.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    rtl.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    let size = Size::new(Au(20), Au(10));
+    let (ltr_left, rtl_left) = (ltr.place(Side::Left, &size), rtl.place(Side::Left, &size));
+    assert_eq!((ltr_left.origin.inline, rtl_left.origin.inline), (Au(30), Au(50)));
+    let (ltr_right, rtl_right) = (ltr.place(Side::Right, &size), rtl.place(Side::Right, &size));
+    assert_eq!((ltr_right.origin.inline, rtl_right.origin.inline), (Au(80), Au(0)));
+    assert_eq!(rtl.line_box(Au(0), Au(10)), (Au(0), Au(70)));
+    assert_eq!(rtl.clearance(Au(0), ClearSide::Left), Au(10));
+    assert_eq!(rtl.clearance(Au(0), ClearSide::Right), Au(0));
+}
+
+#[test]
+fn check_margins() {
+    let margins = Margins::new(Au(5), Au(5), Au(5), Au(5));
+    let no_margins = Margins::new(Au(0), Au(0), Au(0), Au(0));
+    let size = Size::new(Au(30), Au(10));
+    let mut with = Exclusions::new(Au(100));
+    let origin = with.place_with_margins(Side::Left, &size, &margins).origin;
+    assert_eq!((origin.inline, origin.block), (Au(5), Au(5)));
+    with.exclude_with_margins(Side::Left, &origin, &size, &margins);
+    let mut without = Exclusions::new(Au(100));
+    let origin = without.place_with_margins(Side::Left, &size, &no_margins).origin;
+    without.exclude_with_margins(Side::Left, &origin, &size, &no_margins);
+
+    let narrow = Size::new(Au(50), Au(10));
+    let origin = with.place_with_margins(Side::Left, &narrow, &no_margins).origin;
+    assert_eq!((origin.inline, origin.block), (Au(40), Au(0)));
+    let origin = without.place_with_margins(Side::Left, &narrow, &no_margins).origin;
+    assert_eq!((origin.inline, origin.block), (Au(30), Au(0)));
+
+    let wide = Size::new(Au(65), Au(10));
+    assert_eq!(with.place_with_margins(Side::Right, &wide, &no_margins).origin.block, Au(20));
+    assert_eq!(without.place_with_margins(Side::Right, &wide, &no_margins).origin.block, Au(0));
+
+    let placement = with.place_with_margins(Side::Right, &narrow, &margins);
+    assert_eq!((placement.origin.inline, placement.origin.block), (Au(45), Au(5)));
+    with.exclude_with_margins(Side::Right, &placement.origin, &narrow, &margins);
+    assert_eq!(with.line_box(Au(0), Au(20)), (Au(40), Au(40)));
+}
+
+#[test]
+fn check_remove() {
+    fn bands(exclusions: &Exclusions) -> Vec<(Au, Au, Au, Au)> {
+        exclusions.bands().map(|(block_position, band)| {
+            (block_position, band.left(), band.right(), band.length())
+        }).collect()
+    }
+
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(5)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(15)));
+    let before = bands(&exclusions);
+    let top_left = Point::new(Au(0), Au(0));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    assert!(exclusions.remove(Side::Left, top_left, &Size::new(Au(30), Au(10))));
+    assert_eq!(bands(&exclusions), before);
+
+    // A float placed partway down the zone is removed by its own origin and size, and removing
+    // it splits the band that covered both it and the smaller exclusion above it again.
+    let size = Size::new(Au(10), Au(10));
+    let origin = exclusions.place(Side::Left, &size).origin;
+    assert_eq!((origin.inline, origin.block), (Au(10), Au(0)));
+    exclusions.exclude_with_margins(Side::Left, &origin, &size, &Margins::new(Au(0), Au(0),
+                                                                              Au(0), Au(0)));
+    assert_ne!(bands(&exclusions), before);
+    assert!(exclusions.remove(Side::Left, origin, &size));
+    assert_eq!(bands(&exclusions), before);
+
+    assert!(!exclusions.remove(Side::Left, top_left, &Size::new(Au(30), Au(10))));
+    assert!(exclusions.remove(Side::Left, top_left, &Size::new(Au(10), Au(5))));
+    assert!(exclusions.remove(Side::Right, Point::new(Au(80), Au(0)), &Size::new(Au(20), Au(15))));
+    assert_eq!(exclusions.band_count(), 1);
+    assert!(!exclusions.has_exclusions());
+}
+
+#[test]
+fn check_remove_restores_bands() {
+    fn bands(exclusions: &Exclusions) -> Vec<(Au, Band)> {
+        exclusions.bands().collect()
+    }
+
+    for &direction in &[Direction::Ltr, Direction::Rtl] {
+        // The origin of the rectangle that `exclude` excludes, which is on the right for the
+        // `Left` side of a right-to-left zone.
+        let origin = |side, size: Size| {
+            match (direction, side) {
+                (Direction::Ltr, Side::Left) | (Direction::Rtl, Side::Right) => {
+                    Point::new(Au(0), Au(0))
+                }
+                _ => Point::new(Au(100) - size.inline, Au(0)),
+            }
+        };
+        let remove = |exclusions: &mut Exclusions, side, size| {
+            exclusions.remove(side, origin(side, size), &size)
+        };
+        let mut exclusions = Exclusions::with_direction(Au(100), direction);
+        let empty = bands(&exclusions);
+        exclusions.exclude(Side::Left, &Size::new(Au(20), Au(5)));
+        exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+        exclusions.exclude(Side::Right, &Size::new(Au(10), Au(20)));
+        exclusions.exclude(Side::Left, &Size::new(Au(10), Au(15)));
+        let before = bands(&exclusions);
+
+        // Excluding a larger area swallows the bands above it, and removing it must bring them
+        // back.
+        exclusions.exclude(Side::Left, &Size::new(Au(40), Au(25)));
+        assert_eq!(exclusions.band_count(), 3);
+        assert!(remove(&mut exclusions, Side::Left, Size::new(Au(40), Au(25))));
+        assert_eq!(bands(&exclusions), before);
+
+        // Removing exclusions hidden by larger ones leaves the bands alone.
+        exclusions.exclude(Side::Left, &Size::new(Au(5), Au(2)));
+        assert_eq!(bands(&exclusions), before);
+        assert!(remove(&mut exclusions, Side::Left, Size::new(Au(5), Au(2))));
+        assert_eq!(bands(&exclusions), before);
+
+        // The same exclusion twice needs removing twice.
+        exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+        assert!(remove(&mut exclusions, Side::Left, Size::new(Au(30), Au(10))));
+        assert_eq!(bands(&exclusions), before);
+
+        let circle_center = Point::new(Au(50), Au(100));
+        exclusions.exclude_circle_with_band_size(Side::Left, circle_center, Au(40), Au(4));
+        assert!(exclusions.band_count() > 10);
+        assert!(exclusions.remove_circle_with_band_size(Side::Left, circle_center, Au(40), Au(4)));
+        assert_eq!(bands(&exclusions), before);
+        assert!(!exclusions.remove_circle_with_band_size(Side::Left, circle_center, Au(40),
+                                                         Au(4)));
+
+        for &(side, size) in &[(Side::Left, Size::new(Au(30), Au(10))),
+                               (Side::Left, Size::new(Au(20), Au(5))),
+                               (Side::Right, Size::new(Au(10), Au(20))),
+                               (Side::Left, Size::new(Au(10), Au(15)))] {
+            assert!(remove(&mut exclusions, side, size));
+        }
+        assert_eq!(bands(&exclusions), empty);
+        assert!(!exclusions.has_exclusions());
+    }
+}
+
+quickcheck! {
+    fn check_remove_matches_rebuilding(excluded: Vec<(bool, u8, u8)>, index: usize, snap: bool)
+                                       -> bool {
+        let excluded: Vec<(Side, Size)> = excluded.into_iter().map(|(left, inline, block)| {
+            let side = if left { Side::Left } else { Side::Right };
+            (side, Size::new(Au(inline as i32 + 1), Au(block as i32 + 1)))
+        }).collect();
+        if excluded.is_empty() {
+            return true
+        }
+        let index = index % excluded.len();
+        let (mut exclusions, mut rebuilt) = (Exclusions::new(Au(300)), Exclusions::new(Au(300)));
+        for (excluded_index, &(side, size)) in excluded.iter().enumerate() {
+            exclusions.exclude(side, &size);
+            if excluded_index != index {
+                rebuilt.exclude(side, &size);
+            }
+        }
+        if snap {
+            exclusions.snap_to_grid(Au(60));
+            rebuilt.snap_to_grid(Au(60));
+        }
+        let (side, size) = excluded[index];
+        let origin = match side {
+            Side::Left => Point::new(Au(0), Au(0)),
+            Side::Right => Point::new(Au(300) - size.inline, Au(0)),
+        };
+        exclusions.remove(side, origin, &size) &&
+            exclusions.bands().collect::<Vec<_>>() == rebuilt.bands().collect::<Vec<_>>() &&
+            exclusions.has_exclusions() == rebuilt.has_exclusions()
+    }
+}
+
+#[test]
+fn check_content_bottom() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.content_bottom(), Au(0));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(25)));
+    assert_eq!(exclusions.content_bottom(), Au(25));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(40)));
+    exclusions.exclude(Side::Right, &Size::new(Au(40), Au(15)));
+    assert_eq!(exclusions.content_bottom(), Au(40));
+    exclusions.exclude(Side::Right, &Size::new(Au(10), Au(i32::MAX)));
+    assert_eq!(exclusions.content_bottom(), Au(40));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn check_render_ascii() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+    assert_eq!(exclusions.render_ascii(4, 10), concat!("###     ##\n",
+                                                       "###     ##\n",
+                                                       "        ##\n",
+                                                       "        ##\n"));
+    assert_eq!(Exclusions::new(Au(100)).render_ascii(1, 4), "    \n");
+}
+
+#[test]
+fn check_fits() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+
+    assert!(exclusions.fits(Point::new(Au(30), Au(0)), &Size::new(Au(50), Au(10))));
+    assert!(!exclusions.fits(Point::new(Au(29), Au(0)), &Size::new(Au(50), Au(10))));
+    assert!(!exclusions.fits(Point::new(Au(30), Au(0)), &Size::new(Au(51), Au(10))));
+
+    // A box straddling the bottom of the left exclusion must clear it too.
+    assert!(!exclusions.fits(Point::new(Au(10), Au(5)), &Size::new(Au(20), Au(10))));
+    assert!(exclusions.fits(Point::new(Au(10), Au(10)), &Size::new(Au(20), Au(10))));
+    assert!(!exclusions.fits(Point::new(Au(10), Au(10)), &Size::new(Au(71), Au(10))));
+    assert!(exclusions.fits(Point::new(Au(0), Au(20)), &Size::new(Au(100), Au(30))));
+}
+
+#[test]
+fn check_finite_height_exclusions() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    assert_eq!(exclusions.available_inline_size(Au(9)), Au(70));
+    assert_eq!(exclusions.available_inline_size(Au(10)), Au(100));
+    assert_eq!(exclusions.available_inline_size(Au(1_000_000)), Au(100));
+
+    // A shorter, wider exclusion splits the band of a taller one, which resumes below it.
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(50)));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(5)));
+    assert_eq!(exclusions.band_count(), 4);
+    assert_eq!(exclusions.available_inline_size(Au(0)), Au(40));
+    assert_eq!(exclusions.available_inline_size(Au(5)), Au(70));
+    assert_eq!(exclusions.available_inline_size(Au(10)), Au(80));
+    assert_eq!(exclusions.available_inline_size(Au(50)), Au(100));
+    assert_eq!(exclusions.available_inline_size(Au(1_000_000)), Au(100));
+}
+
+#[test]
+fn check_place_best() {
+    let mut exclusions = Exclusions::new(Au(100));
+    let (side, origin) = exclusions.place_best(&Size::new(Au(10), Au(10)));
+    assert_eq!((side, origin.inline, origin.block), (Side::Left, Au(0), Au(0)));
+
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(40), Au(20)));
+    let (side, origin) = exclusions.place_best(&Size::new(Au(30), Au(10)));
+    assert_eq!((side, origin.inline, origin.block), (Side::Left, Au(30), Au(0)));
+    let (side, origin) = exclusions.place_best(&Size::new(Au(50), Au(10)));
+    assert_eq!((side, origin.inline, origin.block), (Side::Left, Au(0), Au(10)));
+
+    let mut exclusions = Exclusions::with_direction(Au(100), Direction::Rtl);
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    let (side, origin) = exclusions.place_best(&Size::new(Au(20), Au(10)));
+    assert_eq!((side, origin.inline, origin.block), (Side::Left, Au(50), Au(0)));
+}
+
+#[test]
+fn check_from_bands() {
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(20)));
+    let restored = Exclusions::from_bands(Au(100), Direction::Ltr, Au(0), exclusions.bands())
+        .unwrap();
+    assert_eq!(restored.band_count(), exclusions.band_count());
+    assert!(restored.has_exclusions());
+    for block_position in &[Au(0), Au(9), Au(10), Au(19), Au(20), Au(1_000_000)] {
+        assert_eq!(restored.available_inline_size(*block_position),
+                   exclusions.available_inline_size(*block_position));
+    }
+
+    let mut restored = restored;
+    assert!(restored.remove(Side::Left, Point::new(Au(0), Au(0)), &Size::new(Au(30), Au(10))));
+    assert_eq!(restored.available_inline_size(Au(0)), Au(80));
+
+    // In a right-to-left zone whose content starts partway down, the physical right insets are
+    // treated as exclusions on the `Left` side.
+    let band = |left, right, length| Band::new(Au(left), Au(right), Au(length));
+    let bands = vec![(Au(5), band(-20, -30, 10)),
+                     (Au(15), band(-20, 0, 5)),
+                     (Au(20), band(0, 0, i32::MAX - 20))];
+    let mut restored = Exclusions::from_bands(Au(100), Direction::Rtl, Au(5), bands.clone())
+        .unwrap();
+    assert_eq!(restored.bands().collect::<Vec<_>>(), bands);
+    assert_eq!(restored.clearance(Au(0), ClearSide::Left), Au(15));
+    assert!(restored.remove(Side::Left, Point::new(Au(70), Au(0)), &Size::new(Au(30), Au(15))));
+    assert_eq!(restored.line_box(Au(5), Au(0)), (Au(20), Au(100)));
+    restored.clear();
+    assert_eq!(restored.bands().collect::<Vec<_>>(), vec![(Au(5), band(0, 0, i32::MAX - 5))]);
+
+    let from_bands = |bands| Exclusions::from_bands(Au(100), Direction::Ltr, Au(0), bands);
+    assert_eq!(from_bands(vec![]).unwrap_err(), BandsError::Empty);
+    assert_eq!(from_bands(vec![(Au(5), band(0, 0, i32::MAX - 5))]).unwrap_err(),
+               BandsError::NotContiguous(Au(5)));
+    assert_eq!(from_bands(vec![(Au(0), band(0, 0, 10)), (Au(20), band(0, 0, 10))]).unwrap_err(),
+               BandsError::NotContiguous(Au(20)));
+    assert_eq!(from_bands(vec![(Au(0), band(0, 0, 0))]).unwrap_err(),
+               BandsError::NotPositive(Au(0)));
+    assert_eq!(from_bands(vec![(Au(0), band(-10, 0, 10)), (Au(10), band(-20, 0, i32::MAX - 10))])
+                   .unwrap_err(),
+               BandsError::NotStaircase(Au(10)));
+    assert_eq!(from_bands(vec![(Au(0), band(0, 0, 10))]).unwrap_err(), BandsError::Unterminated);
+    assert_eq!(Exclusions::from_bands(Au(100), Direction::Ltr, Au(5), bands[1..].to_vec())
+                   .unwrap_err(),
+               BandsError::NotContiguous(Au(15)));
+}
+
+#[test]
+fn check_bands_error_display() {
+    assert_eq!(BandsError::Empty.to_string(), "there are no bands");
+    assert_eq!(BandsError::NotContiguous(Au::from_px(2)).to_string(),
+               "the band at 2px doesn't start where the one above it ends");
+    assert_eq!(BandsError::NotPositive(Au::from_px(2)).to_string(),
+               "the band at 2px isn't tall enough");
+    assert_eq!(BandsError::NotStaircase(Au::from_px(2)).to_string(),
+               "the insets of the band at 2px can't come from any exclusions");
+    assert_eq!(BandsError::Unterminated.to_string(),
+               "the last band doesn't reach the bottom of the zone");
+}
+
+#[test]
+fn check_rectangles() {
+    let rectangle = Rectangle::new(Point::new(Au(10), Au(20)), Size::new(Au(30), Au(40)));
+    assert_eq!((rectangle.inline_end(), rectangle.block_end()), (Au(40), Au(60)));
+
+    assert!(rectangle.contains(Point::new(Au(10), Au(20))));
+    assert!(rectangle.contains(Point::new(Au(39), Au(59))));
+    assert!(!rectangle.contains(Point::new(Au(40), Au(30))));
+    assert!(!rectangle.contains(Point::new(Au(20), Au(60))));
+    assert!(!rectangle.contains(Point::new(Au(9), Au(30))));
+
+    let other = |inline, block| {
+        Rectangle::new(Point::new(Au(inline), Au(block)), Size::new(Au(10), Au(10)))
+    };
+    assert!(rectangle.intersects(&other(35, 55)));
+    assert!(other(35, 55).intersects(&rectangle));
+    assert!(rectangle.intersects(&other(15, 25)));
+    assert!(!rectangle.intersects(&other(40, 30)));
+    assert!(!rectangle.intersects(&other(20, 10)));
+
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(10)));
+    let placed = exclusions.place_rect(Side::Right, &Size::new(Au(20), Au(5)));
+    assert_eq!((placed.origin.inline, placed.origin.block), (Au(80), Au(0)));
+    assert_eq!((placed.size.inline, placed.size.block), (Au(20), Au(5)));
+}
+
+#[test]
+fn check_placement_error_display() {
+    let error = PlacementError::TooWide {
+        requested: Au::from_px(320),
+        available: Au::from_px(280),
+    };
+    assert_eq!(error.to_string(), "object 320px wide exceeds the 280px available");
+    assert_eq!(PlacementError::NoBand.to_string(), "no band can hold the object");
+}
+
+#[test]
+fn check_map_invariants() {
+    let mut map = SplayMap::new();
+    map.check_invariants();
+    for key in &[5, 3, 8, 1, 4, 7, 9, 2, 6] {
+        map.insert(*key, *key * 10);
+        map.check_invariants();
+    }
+    map.get(&4);
+    map.get(&100);
+    map.check_invariants();
+    map.remove(&5);
+    map.remove(&100);
+    map.check_invariants();
+    assert_eq!(map.len(), 8);
+
+    let mut map = SplayMap::from_sorted_iter((0..100).map(|key| (key, ())));
+    map.check_invariants();
+    let mut clone = SplayMap::new();
+    clone.clone_from(&map);
+    clone.check_invariants();
+    clone.clear();
+    clone.check_invariants();
+}
+
+#[test]
+fn check_invariants_in_constant_space() {
+    // Looking up the keys in ascending order leaves a tree as tall as it has nodes, which a
+    // recursive walk would overflow the stack on.
+    let mut map = SplayMap::from_sorted_iter((0..100_000u32).map(|key| (key, key)));
+    for key in 0..100_000 {
+        map.get(&key);
+    }
+    assert_eq!(map.height(), 100_000);
+    let diagnostics = map.diagnostics();
+    map.check_invariants();
+    assert_eq!(map.diagnostics(), diagnostics);
+}
+
+#[test]
+fn check_invariants_restore_the_tree_on_failure() {
+    use std::cell::Cell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::string::String;
+
+    // A key that can be changed from outside the map, to break the order of the keys.
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Key(Rc<Cell<u32>>);
+
+    let keys: Vec<Rc<Cell<u32>>> = (0..100).map(|key| Rc::new(Cell::new(key))).collect();
+    let mut map = SplayMap::from_sorted_iter(keys.iter().map(|key| (Key(key.clone()), ())));
+    let diagnostics = map.diagnostics();
+    keys[40].set(60);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| map.check_invariants()));
+    let message = result.unwrap_err().downcast::<String>().unwrap();
+    assert_eq!(*message, "SplayMap::check_invariants(): Keys are out of order!");
+    keys[40].set(40);
+    assert_eq!(map.diagnostics(), diagnostics);
+    map.check_invariants();
+    assert_eq!(map.len(), 100);
+}
+
+#[test]
+fn check_into_iter_nth_on_balanced_tree() {
+    let map = SplayMap::from_sorted_iter((0..100).map(|key| (key, ())));
+    for n in 0..101 {
+        assert_eq!(map.clone().into_iter().nth(n).map(|(key, _)| key),
+                   if n < 100 { Some(n) } else { None });
+        assert_eq!(map.clone().into_iter().nth_back(n).map(|(key, _)| key),
+                   if n < 100 { Some(99 - n) } else { None });
+    }
+
+    let mut iter = map.into_iter();
+    assert_eq!(iter.nth(10).map(|(key, _)| key), Some(10));
+    assert_eq!(iter.nth_back(10).map(|(key, _)| key), Some(89));
+    assert_eq!(iter.len(), 78);
+    assert_eq!(iter.next().map(|(key, _)| key), Some(11));
+    assert_eq!(iter.next_back().map(|(key, _)| key), Some(88));
+}
+
+#[test]
+fn check_count_range() {
+    let map: SplayMap<u32, ()> = (0..50).map(|key| (key * 2, ())).collect();
+    assert_eq!(map.count_range(..), 50);
+    assert_eq!(map.count_range(10..20), 5);
+    assert_eq!(map.count_range(10..=20), 6);
+    assert_eq!(map.count_range(11..20), 4);
+    assert_eq!(map.count_range(..10), 5);
+    assert_eq!(map.count_range(..=10), 6);
+    assert_eq!(map.count_range(90..), 5);
+    assert_eq!(map.count_range(91..), 4);
+    assert_eq!(map.count_range(200..), 0);
+    assert_eq!(map.count_range(10..10), 0);
+    assert_eq!(map.count_range(11..=11), 0);
+    assert_eq!(map.count_range((Bound::Included(20), Bound::Excluded(10))), 0);
+    assert_eq!(map.count_range((Bound::Excluded(10), Bound::Excluded(20))), 4);
+    assert_eq!(map.count_range((Bound::Excluded(10), Bound::Included(20))), 5);
+    assert_eq!(SplayMap::<u32, ()>::new().count_range(..), 0);
+}
+
+/// A string key that ignores ASCII case when compared.
+#[derive(Clone, Copy, Debug)]
+struct CaseInsensitive(&'static str);
+
+impl PartialEq for CaseInsensitive {
+    fn eq(&self, other: &CaseInsensitive) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+
+impl Eq for CaseInsensitive {}
+
+impl PartialOrd for CaseInsensitive {
+    fn partial_cmp(&self, other: &CaseInsensitive) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CaseInsensitive {
+    fn cmp(&self, other: &CaseInsensitive) -> cmp::Ordering {
+        let lowercase = |key: &'static str| key.bytes().map(|byte| byte.to_ascii_lowercase());
+        lowercase(self.0).cmp(lowercase(other.0))
+    }
+}
+
+#[test]
+fn check_replace() {
+    let mut map = SplayMap::new();
+    assert!(map.replace(CaseInsensitive("b"), 1).is_none());
+    assert!(map.replace(CaseInsensitive("a"), 2).is_none());
+
+    map.insert(CaseInsensitive("B"), 3);
+    let (key, value) = map.into_iter().nth(1).unwrap();
+    assert_eq!((key.0, value), ("b", 3));
+
+    let mut map: SplayMap<_, _> = vec![(CaseInsensitive("a"), 2), (CaseInsensitive("b"), 1)]
+        .into_iter()
+        .collect();
+    let (old_key, old_value) = map.replace(CaseInsensitive("B"), 3).unwrap();
+    assert_eq!((old_key.0, old_value), ("b", 1));
+    assert_eq!(map.len(), 2);
+    let pairs: Vec<(&str, u32)> = map.into_iter().map(|(key, value)| (key.0, value)).collect();
+    assert_eq!(pairs, vec![("a", 2), ("B", 3)]);
+}
+
+#[test]
+fn check_remove_entry() {
+    let mut map: SplayMap<_, _> = vec![(CaseInsensitive("a"), 1), (CaseInsensitive("B"), 2)]
+        .into_iter()
+        .collect();
+    let (key, value) = map.remove_entry(&CaseInsensitive("b")).unwrap();
+    assert_eq!((key.0, value), ("B", 2));
+    assert!(map.remove_entry(&CaseInsensitive("b")).is_none());
+    map.check_invariants();
+    assert_eq!(map.remove(&CaseInsensitive("A")), Some(1));
+    assert!(map.is_empty());
+}
+
+#[test]
+fn check_try_insert() {
+    let mut map = SplayMap::new();
+    *map.try_insert(1, 10).unwrap() += 1;
+    assert_eq!(*map.try_insert(3, 30).unwrap(), 30);
+    assert_eq!(*map.try_insert(2, 20).unwrap(), 20);
+    {
+        let error = map.try_insert(1, 100).unwrap_err();
+        assert_eq!((error.key, error.value, *error.existing), (1, 100, 11));
+        assert_eq!(error.to_string(), "failed to insert 100, key 1 already exists with value 11");
+        *error.existing = 12;
+    }
+    assert_eq!(map.len(), 3);
+    map.check_invariants();
+    assert_eq!(map.into_iter().collect::<Vec<_>>(), vec![(1, 12), (2, 20), (3, 30)]);
+}
+
+#[test]
+fn check_cursors() {
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key * 10, key)).collect();
+    {
+        let mut cursor = map.cursor_front();
+        let mut keys = vec![];
+        while let Some((&key, _)) = cursor.key_value() {
+            keys.push(key);
+            cursor.move_next();
+        }
+        assert_eq!(keys, (0..10).map(|key| key * 10).collect::<Vec<_>>());
+        cursor.move_next();
+        assert_eq!(cursor.key_value().map(|(&key, _)| key), Some(0));
+        cursor.move_prev();
+        assert!(cursor.key_value().is_none());
+        cursor.move_prev();
+        assert_eq!(cursor.key_value().map(|(&key, _)| key), Some(90));
+    }
+    {
+        let mut cursor = map.cursor_at(&50);
+        *cursor.key_value().unwrap().1 = 500;
+        cursor.insert_after(55, 5);
+        cursor.insert_before(45, 4);
+        cursor.move_next();
+        assert_eq!(cursor.key_value().map(|(&key, &mut value)| (key, value)), Some((55, 5)));
+        assert_eq!(cursor.remove_current(), Some((55, 5)));
+        assert_eq!(cursor.key_value().map(|(&key, _)| key), Some(60));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.key_value().map(|(&key, _)| key), Some(45));
+    }
+    assert!(map.cursor_at(&51).key_value().is_none());
+    {
+        let mut cursor = map.cursor_back();
+        assert_eq!(cursor.remove_current(), Some((90, 9)));
+        assert!(cursor.key_value().is_none());
+        assert!(cursor.remove_current().is_none());
+        cursor.insert_before(100, 10);
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some((0, 0)));
+        cursor.move_prev();
+        assert!(cursor.key_value().is_none());
+        cursor.insert_after(5, 0);
+    }
+    map.check_invariants();
+    assert_eq!(map.len(), 11);
+    assert_eq!(map.get(&50), Some(&500));
+    let keys: Vec<u32> = map.into_iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, vec![5, 10, 20, 30, 40, 45, 50, 60, 70, 80, 100]);
+}
+
+#[test]
+#[should_panic(expected = "Key is out of order")]
+fn check_cursor_insert_out_of_order() {
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key * 10, key)).collect();
+    map.cursor_at(&50).insert_after(65, 0);
+}
+
+#[test]
+fn check_shared_lookups() {
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key, key * 10)).collect();
+    // Back when `get` splayed under `&self`, holding the first reference across the second lookup
+    // was undefined behavior that Miri would catch.
+    let first = map.get_no_splay(&3);
+    let second = &map[&7];
+    assert_eq!((first, second), (Some(&30), &70));
+    assert_eq!(map.get_no_splay(&10), None);
+    assert_eq!(map.get(&5), Some(&50));
+    assert_eq!(map.get(&11), None);
+    map.check_invariants();
+}
+
+#[test]
+fn check_retain_keys() {
+    let mut map: SplayMap<u32, u32> = (0..20).map(|key| (key, key * 10)).collect();
+    map.retain_keys(|key| key % 3 == 0);
+    map.check_invariants();
+    assert_eq!(map.len(), 7);
+    assert_eq!(map.into_iter().collect::<Vec<_>>(),
+               (0..20).filter(|key| key % 3 == 0).map(|key| (key, key * 10)).collect::<Vec<_>>());
+}
+
+#[test]
+fn check_map_values() {
+    let mut map: SplayMap<u32, u32> =
+        [5, 3, 8, 1, 4, 7, 9, 2, 6].iter().map(|&key| (key, key)).collect();
+    map.get(&4);
+    let mut visited = vec![];
+    let mut map = map.map_values(|value| {
+        visited.push(value);
+        format!("{}", value * 10)
+    });
+    map.check_invariants();
+    assert_eq!(map.len(), 9);
+    assert_eq!(visited, (1..10).collect::<Vec<_>>());
+    assert_eq!(map.into_iter().collect::<Vec<_>>(),
+               (1..10).map(|key| (key, format!("{}", key * 10))).collect::<Vec<_>>());
+}
+
+#[test]
+fn check_exclude_circle() {
+    let mut exclusions = Exclusions::new(Au(1000));
+    let center = Point::new(Au(50), Au(200));
+    exclusions.exclude_circle_with_band_size(Side::Left, center, Au(100), Au(10));
+    let inset = |block_position| 1000 - exclusions.available_inline_size(Au(block_position)).0;
+    assert_eq!(inset(200), 150);
+    assert_eq!(inset(150), 150);
+    let lower_insets: Vec<i32> = (200..300).step_by(10).map(inset).collect();
+    assert!(lower_insets.windows(2).all(|pair| pair[0] > pair[1]));
+    assert_eq!(inset(250), 50 + 86);
+    assert!(inset(299) > 0);
+    assert_eq!(inset(300), 0);
+
+    let mut exclusions = Exclusions::new(Au(1000));
+    exclusions.exclude_circle(Side::Right, Point::new(Au(900), Au(600)), Au(600));
+    assert_eq!(exclusions.line_box(Au(600), Au(0)), (Au(0), Au(300)));
+    assert_eq!(exclusions.line_box(Au(1200), Au(0)), (Au(0), Au(1000)));
+}
+
+#[test]
+fn check_leftmost_and_rightmost_available() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.leftmost_available(Au(0)), Au(0));
+    assert_eq!(exclusions.rightmost_available(Au(0)), Au(100));
+
+    exclusions.exclude(Side::Left, &Size::new(Au(30), Au(20)));
+    assert_eq!(exclusions.leftmost_available(Au(10)), Au(30));
+    assert_eq!(exclusions.rightmost_available(Au(10)), Au(100));
+    assert_eq!(exclusions.leftmost_available(Au(20)), Au(0));
+
+    exclusions.exclude(Side::Right, &Size::new(Au(25), Au(40)));
+    assert_eq!(exclusions.leftmost_available(Au(10)), Au(30));
+    assert_eq!(exclusions.rightmost_available(Au(10)), Au(75));
+    assert_eq!(exclusions.leftmost_available(Au(30)), Au(0));
+    assert_eq!(exclusions.rightmost_available(Au(30)), Au(75));
+    assert_eq!(exclusions.rightmost_available(Au(40)), Au(100));
+}
+
+#[test]
+fn check_heights_through_rotations() {
+    // Starting from a balanced tree of height 3, look up keys that take each of the zig, zig-zig,
+    // and zig-zag cases of the splay on either side.
+    for &(key, height) in &[(2, 4), (1, 5), (3, 4), (6, 4), (7, 5), (5, 4)] {
+        let mut map = SplayMap::from_sorted_iter((1..8).map(|key| (key, ())));
+        assert_eq!(map.height(), 3);
+        map.get(&key);
+        map.check_invariants();
+        assert_eq!(map.height(), height);
+    }
+
+    // A lookup that misses stops partway through a rotation.
+    let mut map = SplayMap::from_sorted_iter((1..8).map(|key| (key * 2, ())));
+    map.get(&1);
+    map.check_invariants();
+    map.get(&15);
+    map.check_invariants();
+}
+
+#[test]
+fn check_is_degenerate() {
+    let mut map = SplayMap::new();
+    assert_eq!(map.height(), 0);
+    assert!(!map.is_degenerate());
+    for key in 0..100 {
+        map.insert(key, ());
+    }
+    map.check_invariants();
+    assert_eq!(map.height(), 100);
+    assert!(map.is_degenerate());
+    // Splaying the deepest key roughly halves the depth of the path to it.
+    map.get(&0);
+    map.check_invariants();
+    assert_eq!(map.height(), 51);
+
+    let map = SplayMap::from_sorted_iter((0..100).map(|key| (key, ())));
+    assert_eq!(map.height(), 7);
+    assert!(!map.is_degenerate());
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn check_stats() {
+    let mut map: SplayMap<u32, ()> = (0..100).map(|key| (key * 7 % 100, ())).collect();
+    map.get(&42);
+    let warm = map.stats();
+    for _ in 0..10 {
+        map.get(&42);
+    }
+    let stats = map.stats();
+    assert_eq!(stats.rotations, warm.rotations);
+    assert_eq!(stats.lookups, warm.lookups + 10);
+    assert_eq!(stats.comparisons, warm.comparisons + 10);
+
+    map.get_no_splay(&0);
+    assert_eq!(map.stats().rotations, stats.rotations);
+    assert_eq!(map.stats().lookups, stats.lookups + 1);
+    assert!(map.stats().comparisons > stats.comparisons);
+}
+
+#[test]
+fn check_sorted_vecs() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key * 10)).collect();
+    map.get(&4);
+    let expected = vec![(1, 10), (3, 30), (4, 40), (5, 50), (8, 80)];
+    assert_eq!(map.to_sorted_vec(), expected);
+    assert_eq!(map.len(), 5);
+    assert_eq!(map.into_sorted_vec(), expected);
+    assert!(SplayMap::<u32, u32>::new().to_sorted_vec().is_empty());
+}
+
+#[test]
+fn check_get_mut_or_default() {
+    let mut map: SplayMap<u32, Vec<&str>> = SplayMap::new();
+    for &(key, word) in &[(2, "b"), (1, "a"), (2, "c"), (3, "d"), (1, "e")] {
+        map.get_mut_or_default(key).push(word);
+        map.check_invariants();
+    }
+    assert_eq!(map.len(), 3);
+    assert!(map.get_mut_or_default(4).is_empty());
+    assert_eq!(map.into_sorted_vec(),
+               vec![(1, vec!["a", "e"]), (2, vec!["b", "c"]), (3, vec!["d"]), (4, vec![])]);
+}
+
+#[test]
+fn check_get_or_insert_with() {
+    let mut map: SplayMap<u32, u32> = vec![(1, 10), (3, 30)].into_iter().collect();
+    let mut calls = 0;
+    for &key in &[3, 2, 1, 2] {
+        *map.get_or_insert_with(key, || {
+            calls += 1;
+            key * 100
+        }) += 1;
+        map.check_invariants();
+    }
+    assert_eq!(calls, 1);
+    assert_eq!(map.into_sorted_vec(), vec![(1, 11), (2, 202), (3, 31)]);
+}
+
+#[test]
+fn check_iter_from() {
+    let mut map: SplayMap<u32, u32> = (0..20).map(|key| (key * 5, key)).collect();
+    map.get(&50);
+    let iter = map.iter_from(&33);
+    assert_eq!(iter.len(), 13);
+    assert_eq!(iter.map(|(&key, _)| key).collect::<Vec<_>>(),
+               (7..20).map(|key| key * 5).collect::<Vec<_>>());
+    assert_eq!(map.iter_from(&35).next(), Some((&35, &7)));
+    assert_eq!(map.iter_from(&0).count(), 20);
+    assert!(map.iter_from(&96).next().is_none());
+    map.check_invariants();
+}
+
+#[test]
+fn check_snap_to_grid() {
+    let mut exclusions = Exclusions::new(Au(1000));
+    exclusions.exclude(Side::Left, &Size::new(Au(95), Au(130)));
+    exclusions.exclude(Side::Right, &Size::new(Au(40), Au(170)));
+    exclusions.exclude(Side::Left, &Size::new(Au(70), Au(200)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(250)));
+    exclusions.exclude(Side::Left, &Size::new(Au(50), Au(20)));
+    exclusions.snap_to_grid(Au(60));
+
+    let bands: Vec<(Au, Band)> = exclusions.bands().collect();
+    for &(block_position, band) in &bands {
+        assert_eq!(block_position.0 % 60, 0);
+        assert!(band.length() > Au(0));
+        assert!(band.length() == Au::MAX - block_position || band.length().0 % 60 == 0);
+        assert_eq!((band.left().0 % 60, band.right().0 % 60), (0, 0));
+    }
+    let insets: Vec<(i32, i32, i32)> = bands.iter().map(|&(block_position, band)| {
+        (block_position.0, band.left().0, band.right().0)
+    }).collect();
+    assert_eq!(insets, vec![(0, -120, -60), (120, -60, -60), (180, 0, 0)]);
+    assert!(!exclusions.remove(Side::Right,
+                               Point::new(Au(940), Au(0)),
+                               &Size::new(Au(60), Au(180))));
+    assert!(exclusions.remove(Side::Right,
+                              Point::new(Au(960), Au(0)),
+                              &Size::new(Au(40), Au(170))));
+    assert_eq!(exclusions.line_box(Au(0), Au(0)), (Au(120), Au(1000)));
+    // This one rounded down to nothing, so removing it leaves the bands alone.
+    let bands: Vec<(Au, Band)> = exclusions.bands().collect();
+    assert!(exclusions.remove(Side::Right,
+                              Point::new(Au(980), Au(0)),
+                              &Size::new(Au(20), Au(250))));
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(), bands);
+
+    // A float placed partway down an off-grid zone is removed by its own size after snapping, and
+    // the first band starts on the grid.
+    let mut exclusions = Exclusions::with_origin(Au(1000), Au(50));
+    let size = Size::new(Au(95), Au(130));
+    let origin = exclusions.place(Side::Left, &size).origin;
+    exclusions.exclude(Side::Left, &Size::new(origin.inline + size.inline,
+                                              origin.block + size.block));
+    exclusions.snap_to_grid(Au(60));
+    assert_eq!(exclusions.bands().map(|(block_position, band)| {
+        (block_position.0, band.left().0)
+    }).collect::<Vec<_>>(), vec![(60, -120), (180, 0)]);
+    assert!(exclusions.remove(Side::Left, origin, &size));
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+               vec![(Au(60), Band::new(Au(0), Au(0), Au::MAX - Au(60)))]);
+    assert!(!exclusions.has_exclusions());
+}
+
+#[test]
+fn check_first_and_last_entry() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key * 10)).collect();
+    {
+        let mut entry = map.first_entry().unwrap();
+        assert_eq!((*entry.key(), *entry.get()), (1, 10));
+        *entry.get_mut() += 1;
+        assert_eq!(entry.insert(12), 11);
+    }
+    map.check_invariants();
+    assert_eq!(map.get(&1), Some(&12));
+    {
+        let entry = map.last_entry().unwrap();
+        assert_eq!(*entry.key(), 8);
+        assert_eq!(entry.remove_entry(), (8, 80));
+    }
+    map.check_invariants();
+    assert_eq!(map.last_entry().unwrap().remove(), 50);
+    *map.first_entry().unwrap().into_mut() += 1;
+    assert_eq!(map.into_sorted_vec(), vec![(1, 13), (3, 30), (4, 40)]);
+    assert!(SplayMap::<u32, u32>::new().first_entry().is_none());
+}
+
+#[test]
+fn check_max_inline_run() {
+    let mut exclusions = Exclusions::new(Au(100));
+    assert_eq!(exclusions.max_inline_run(Au(0), Au(50)), Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(20), Au(10)));
+    exclusions.exclude(Side::Right, &Size::new(Au(30), Au(30)));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(20)));
+    exclusions.exclude(Side::Right, &Size::new(Au(10), Au(40)));
+
+    // The band above 20 is the narrowest one that the span overlaps.
+    assert_eq!(exclusions.max_inline_run(Au(15), Au(20)), Au(10));
+    assert_eq!(exclusions.max_inline_run(Au(20), Au(15)), Au(70));
+    assert_eq!(exclusions.max_inline_run(Au(25), Au(0)), Au(70));
+    assert_eq!(exclusions.max_inline_run(Au(40), Au(100)), Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(80), Au(5)));
+    assert_eq!(exclusions.max_inline_run(Au(0), Au(10)), Au(0));
+}
+
+#[test]
+fn check_band_equality() {
+    let band = Band::new(Au(-10), Au(-20), Au(30));
+    assert_eq!(band, Band::new(Au(-10), Au(-20), Au(30)));
+    assert!(band != Band::new(Au(-10), Au(-20), Au(40)));
+    assert!(band.can_merge_with(&Band::new(Au(-10), Au(-20), Au(40))));
+    assert!(!band.can_merge_with(&Band::new(Au(-10), Au(0), Au(30))));
+
+    let mut exclusions = Exclusions::new(Au(100));
+    exclusions.exclude(Side::Left, &Size::new(Au(10), Au(30)));
+    exclusions.exclude(Side::Right, &Size::new(Au(20), Au(30)));
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+               vec![(Au(0), Band::new(Au(-10), Au(-20), Au(30))),
+                    (Au(30), Band::new(Au(0), Au(0), Au::MAX - Au(30)))]);
+}
+
+#[test]
+fn check_merge_with() {
+    let mut counts: SplayMap<&str, u32> = vec![("a", 1), ("b", 2), ("c", 3)].into_iter().collect();
+    let other = vec![("b", 10), ("d", 20), ("c", 30)].into_iter().collect();
+    counts.merge_with(other, |_, existing, incoming| *existing += incoming);
+    counts.check_invariants();
+    assert_eq!(counts.into_sorted_vec(), vec![("a", 1), ("b", 12), ("c", 33), ("d", 20)]);
+}
+
+#[test]
+fn check_with_origin() {
+    let mut exclusions = Exclusions::with_origin(Au(100), Au(50));
+    assert_eq!(exclusions.bands().next().map(|(block_position, _)| block_position), Some(Au(50)));
+    exclusions.exclude(Side::Left, &Size::new(Au(40), Au(30)));
+    assert!(!exclusions.has_exclusions());
+
+    let size = Size::new(Au(30), Au(20));
+    let no_margins = Margins::new(Au(0), Au(0), Au(0), Au(0));
+    let origin = exclusions.place_with_margins(Side::Left, &size, &no_margins).origin;
+    assert_eq!((origin.inline, origin.block), (Au(0), Au(50)));
+    exclusions.exclude_with_margins(Side::Left, &origin, &size, &no_margins);
+    let origin = exclusions.place(Side::Left, &size).origin;
+    assert_eq!((origin.inline, origin.block), (Au(30), Au(50)));
+    exclusions.exclude(Side::Left, &Size::new(Au(60), Au(70)));
+    let origin = exclusions.place(Side::Right, &Size::new(Au(50), Au(10))).origin;
+    assert_eq!((origin.inline, origin.block), (Au(50), Au(70)));
+
+    assert_eq!(exclusions.clearance(Au(0), ClearSide::Left), Au(70));
+    assert_eq!(exclusions.available_inline_size(Au(60)), Au(40));
+    assert!(exclusions.remove(Side::Left, Point::new(Au(0), Au(0)), &Size::new(Au(60), Au(70))));
+    assert_eq!(exclusions.available_inline_size(Au(60)), Au(70));
+    exclusions.clear();
+    assert_eq!(exclusions.bands().collect::<Vec<_>>(),
+               vec![(Au(50), Band::new(Au(0), Au(0), Au::MAX - Au(50)))]);
+}
+
+#[test]
+fn check_fold_values_mut() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key)).collect();
+    map.get(&4);
+    let total = map.fold_values_mut(0, |sum, _, value| {
+        *value += sum;
+        *value
+    });
+    assert_eq!(total, 21);
+    map.check_invariants();
+    assert_eq!(map.into_sorted_vec(), vec![(1, 1), (3, 4), (4, 8), (5, 13), (8, 21)]);
+}
+
+#[test]
+fn check_iter() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key * 10)).collect();
+    {
+        let mut iter = map.iter();
+        assert_eq!(iter.size_hint(), (5, Some(5)));
+        assert_eq!(iter.next(), Some((&1, &10)));
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.map(|(&key, _)| key).collect::<Vec<_>>(), vec![3, 4, 5, 8]);
+    }
+    map.insert(2, 20);
+    assert_eq!(map.iter().map(|(&key, _)| key).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5, 8]);
+    assert!(SplayMap::<u32, u32>::new().iter().next().is_none());
+}
+
+#[test]
+fn check_keys_and_values() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key * 10)).collect();
+    assert_eq!(map.keys().len(), 5);
+    assert_eq!(map.keys().cloned().collect::<Vec<_>>(), vec![1, 3, 4, 5, 8]);
+    assert_eq!(map.values().cloned().collect::<Vec<_>>(), vec![10, 30, 40, 50, 80]);
+    for (index, value) in map.values_mut().enumerate() {
+        *value += index as u32;
+    }
+    assert_eq!(map.iter_mut().size_hint(), (5, Some(5)));
+    for (key, value) in map.iter_mut() {
+        *value += key;
+    }
+    map.check_invariants();
+    assert_eq!(map.into_sorted_vec(), vec![(1, 11), (3, 34), (4, 46), (5, 58), (8, 92)]);
+}
+
+#[test]
+fn check_len() {
+    let mut map = SplayMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.insert(1, "one"), None);
+    assert_eq!(map.insert(2, "two"), None);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.insert(1, "uno"), Some("one"));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.remove(&3), None);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.remove(&1), Some("uno"));
+    assert_eq!(map.len(), 1);
+    assert!(!map.is_empty());
+    map.insert(3, "three");
+    map.clear();
+    assert_eq!(map.len(), 0);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn check_contains_key() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key * 10)).collect();
+    assert!(map.contains_key(&4));
+    assert!(!map.contains_key(&6));
+    assert_eq!(map.first_entry().map(|entry| *entry.key()), Some(1));
+    assert!(map.contains_key(&8));
+    map.check_invariants();
+    assert!(!SplayMap::<u32, u32>::new().contains_key(&1));
+}
+
+#[test]
+fn check_entry() {
+    let mut map: SplayMap<&str, u32> = SplayMap::new();
+    for word in "the cat saw the other cat and the dog".split(' ') {
+        *map.entry(word).or_insert(0) += 1;
+    }
+    map.check_invariants();
+    assert_eq!(map.get(&"the"), Some(&3));
+    assert_eq!(map.get(&"cat"), Some(&2));
+
+    assert_eq!(*map.entry("dog").and_modify(|count| *count *= 10).or_insert(0), 10);
+    assert_eq!(*map.entry("eel").and_modify(|count| *count *= 10).or_insert(7), 7);
+    assert_eq!(*map.entry("fox").or_insert_with(|| 4), 4);
+    assert_eq!(*map.entry("gnu").or_insert_with_key(|key| key.len() as u32), 3);
+    assert_eq!(*map.entry("hen").or_default(), 0);
+    assert_eq!(map.entry("the").key(), &"the");
+    match map.entry("yak") {
+        Entry::Vacant(entry) => assert_eq!(entry.into_key(), "yak"),
+        Entry::Occupied(_) => panic!("yak shouldn't be in the map"),
+    }
+    match map.entry("saw") {
+        Entry::Occupied(entry) => assert_eq!(entry.remove(), 1),
+        Entry::Vacant(_) => panic!("saw should be in the map"),
+    }
+    map.check_invariants();
+    assert_eq!(map.len(), 9);
+    assert!(!map.contains_key(&"yak"));
+}
+
+#[test]
+fn check_range() {
+    let mut map: SplayMap<u32, u32> = (0..20).map(|key| (key * 5, key)).collect();
+    map.get(&50);
+    let keys = |range: (Bound<u32>, Bound<u32>)| {
+        map.range(range).map(|(&key, _)| key).collect::<Vec<_>>()
+    };
+    assert_eq!(keys((Bound::Included(10), Bound::Excluded(25))), vec![10, 15, 20]);
+    assert_eq!(keys((Bound::Excluded(10), Bound::Included(25))), vec![15, 20, 25]);
+    assert_eq!(keys((Bound::Included(11), Bound::Included(24))), vec![15, 20]);
+    assert_eq!(keys((Bound::Unbounded, Bound::Excluded(10))), vec![0, 5]);
+    assert_eq!(keys((Bound::Excluded(85), Bound::Unbounded)), vec![90, 95]);
+    assert_eq!(keys((Bound::Unbounded, Bound::Unbounded)).len(), 20);
+    assert!(keys((Bound::Included(10), Bound::Excluded(10))).is_empty());
+    assert!(keys((Bound::Included(30), Bound::Included(10))).is_empty());
+    assert!(keys((Bound::Excluded(95), Bound::Unbounded)).is_empty());
+    assert_eq!(map.range(10..=20).len(), 3);
+    assert_eq!(map.range(..).next(), Some((&0, &0)));
+}
+
+#[test]
+fn check_range_mut() {
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key * 10, key)).collect();
+    map.get(&50);
+    assert_eq!(map.range_mut(25..60).len(), 3);
+    for (&key, value) in map.range_mut(25..60) {
+        *value += key;
+    }
+    assert_eq!(map.range_mut((Bound::Excluded(90), Bound::Unbounded)).next(), None);
+    assert_eq!(map.range_mut((Bound::Included(50), Bound::Excluded(30))).next(), None);
+    map.check_invariants();
+    assert_eq!(map.into_sorted_vec(),
+               vec![(0, 0), (10, 1), (20, 2), (30, 33), (40, 44), (50, 55), (60, 6), (70, 7),
+                    (80, 8), (90, 9)]);
+}
+
+#[test]
+fn check_first_and_last_key_value() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key * 10)).collect();
+    map.get(&4);
+    assert_eq!(map.first_key_value(), Some((&1, &10)));
+    assert_eq!(map.last_key_value(), Some((&8, &80)));
+    assert_eq!(map.iter().next(), Some((&1, &10)));
+    assert_eq!(SplayMap::<u32, u32>::new().first_key_value(), None);
+    assert_eq!(SplayMap::<u32, u32>::new().last_key_value(), None);
+}
+
+#[test]
+fn check_pop_first_and_last() {
+    let mut map: SplayMap<u32, u32> = [5, 3, 8, 1, 4].iter().map(|&key| (key, key * 10)).collect();
+    assert_eq!(map.pop_first(), Some((1, 10)));
+    assert_eq!(map.pop_last(), Some((8, 80)));
+    map.check_invariants();
+    assert_eq!(map.len(), 3);
+    let mut keys = vec![];
+    while let Some((key, _)) = map.pop_last() {
+        keys.push(key);
+        map.check_invariants();
+    }
+    assert_eq!(keys, vec![5, 4, 3]);
+    assert!(map.is_empty());
+    assert_eq!(map.pop_first(), None);
+    assert_eq!(map.pop_last(), None);
+}
+
+#[test]
+fn check_split_off() {
+    let keys = |map: &SplayMap<u32, u32>| map.keys().cloned().collect::<Vec<_>>();
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key * 10, key)).collect();
+    let mut split = map.split_off(&50);
+    map.check_invariants();
+    split.check_invariants();
+    assert_eq!((keys(&map), keys(&split)), (vec![0, 10, 20, 30, 40], vec![50, 60, 70, 80, 90]));
+
+    let mut split = map.split_off(&25);
+    map.check_invariants();
+    split.check_invariants();
+    assert_eq!((map.len(), split.len()), (3, 2));
+    assert_eq!((keys(&map), keys(&split)), (vec![0, 10, 20], vec![30, 40]));
+
+    assert!(map.split_off(&100).is_empty());
+    assert_eq!(map.len(), 3);
+    let split = map.split_off(&0);
+    assert!(map.is_empty());
+    assert_eq!(split.len(), 3);
+    assert!(map.split_off(&0).is_empty());
+}
+
+#[test]
+fn check_append() {
+    let pairs = |keys: &[u32]| keys.iter().map(|&key| (key, key * 10)).collect::<SplayMap<_, _>>();
+    let mut map = pairs(&[1, 2, 3]);
+    let mut other = pairs(&[7, 5, 6]);
+    map.append(&mut other);
+    map.check_invariants();
+    assert!(other.is_empty());
+    assert_eq!(map.keys().cloned().collect::<Vec<_>>(), vec![1, 2, 3, 5, 6, 7]);
+
+    let mut other = pairs(&[0]);
+    map.append(&mut other);
+    map.check_invariants();
+    assert_eq!((map.len(), other.len()), (7, 0));
+    assert_eq!(map.first_key_value(), Some((&0, &0)));
+
+    let mut other: SplayMap<u32, u32> = vec![(3, 33), (4, 44), (8, 88)].into_iter().collect();
+    map.append(&mut other);
+    map.check_invariants();
+    assert_eq!((map.len(), other.len()), (9, 0));
+    assert_eq!((map[&3], map[&4]), (33, 44));
+
+    let mut empty = SplayMap::new();
+    empty.append(&mut map);
+    assert_eq!((empty.len(), map.len()), (9, 0));
+    empty.append(&mut map);
+    assert_eq!(empty.len(), 9);
+}
+
+#[test]
+fn check_retain() {
+    let mut map: SplayMap<u32, u32> = (0..20).map(|key| (key, key)).collect();
+    map.get(&7);
+    let height = map.height();
+    map.retain(|_, value| {
+        *value *= 2;
+        true
+    });
+    assert_eq!((map.len(), map.height()), (20, height));
+    map.retain(|&key, value| {
+        *value += 1;
+        key % 4 == 1
+    });
+    map.check_invariants();
+    assert_eq!(map.into_sorted_vec(), vec![(1, 3), (5, 11), (9, 19), (13, 27), (17, 35)]);
+
+    let mut map: SplayMap<u32, u32> = (0..20).map(|key| (key, key)).collect();
+    map.retain(|_, _| false);
+    map.check_invariants();
+    assert!(map.is_empty());
+    map.insert(1, 1);
+    assert_eq!(map.len(), 1);
+}
+
+#[test]
+fn check_eq() {
+    let mut forward: SplayMap<u32, u32> = (0..10).map(|key| (key, key * 3)).collect();
+    let backward: SplayMap<u32, u32> = (0..10).rev().map(|key| (key, key * 3)).collect();
+    forward.get(&4);
+    assert!(forward.height() != backward.height());
+    assert_eq!(forward, backward);
+    forward.insert(4, 0);
+    assert!(forward != backward);
+    forward.remove(&4);
+    assert!(forward != backward);
+    assert_eq!(SplayMap::<u32, u32>::new(), SplayMap::new());
+}
+
+#[test]
+fn check_debug() {
+    let mut map: SplayMap<u32, &str> = vec![(3, "c"), (1, "a"), (2, "b")].into_iter().collect();
+    let height = map.height();
+    assert_eq!(format!("{:?}", map), r#"{1: "a", 2: "b", 3: "c"}"#);
+    assert_eq!(map.height(), height);
+    map.check_invariants();
+    assert_eq!(format!("{:?}", SplayMap::<u32, u32>::new()), "{}");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn check_deserialize() {
+    use serde::de::Deserialize;
+    use serde::de::value::{Error, MapDeserializer};
+
+    let pairs = vec![(3, 30), (1, 10), (2, 20), (1, 11)];
+    let mut deserializer: MapDeserializer<_, u32, u32, Error> =
+        MapDeserializer::new(pairs.into_iter(), 4);
+    let mut map: SplayMap<u32, u32> = Deserialize::deserialize(&mut deserializer).unwrap();
+    map.check_invariants();
+    assert_eq!(map.into_sorted_vec(), vec![(1, 11), (2, 20), (3, 30)]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn check_json_round_trip() {
+    use serde_json;
+    use std::string::String;
+
+    let map: SplayMap<String, u32> =
+        vec![("c", 3), ("a", 1), ("b", 2)].into_iter().map(|(key, value)| {
+            (key.to_string(), value)
+        }).collect();
+    let json = serde_json::to_string(&map).unwrap();
+    assert_eq!(json, r#"{"a":1,"b":2,"c":3}"#);
+    let mut round_tripped: SplayMap<String, u32> = serde_json::from_str(&json).unwrap();
+    round_tripped.check_invariants();
+    assert_eq!(round_tripped, map);
+
+    let empty: SplayMap<String, u32> = SplayMap::new();
+    let json = serde_json::to_string(&empty).unwrap();
+    assert_eq!(json, "{}");
+    let round_tripped: SplayMap<String, u32> = serde_json::from_str(&json).unwrap();
+    assert!(round_tripped.is_empty());
+
+    let duplicated: SplayMap<String, u32> = serde_json::from_str(r#"{"b":1,"a":2,"b":3}"#).unwrap();
+    assert_eq!(duplicated.into_sorted_vec(), vec![("a".to_string(), 2), ("b".to_string(), 3)]);
+}
+
+#[test]
+fn check_drain() {
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key, key * 2)).collect();
+    let pairs: Vec<(u32, u32)> = map.drain().collect();
+    assert_eq!(pairs, (0..10).map(|key| (key, key * 2)).collect::<Vec<_>>());
+    assert!(map.is_empty());
+    map.insert(3, 4);
+    map.insert(1, 2);
+    map.check_invariants();
+    assert_eq!(map.to_sorted_vec(), vec![(1, 2), (3, 4)]);
+
+    let mut map = SplayMap::from_sorted_iter((0..100_000u32).map(|key| (key, key)));
+    {
+        let mut drain = map.drain();
+        assert_eq!(drain.len(), 100_000);
+        assert_eq!(drain.next(), Some((0, 0)));
+        assert_eq!(drain.next_back(), Some((99_999, 99_999)));
+    }
+    assert_eq!(map.len(), 0);
+    map.check_invariants();
+    map.insert(5, 5);
+    assert_eq!(map.to_sorted_vec(), vec![(5, 5)]);
+}
+
+
+#[test]
+fn check_upper_bound_with() {
+    let mut map: SplayMap<u32, u32> = (1..20).map(|key| (key * 10, key)).collect();
+    map.get(&10);
+    for target in 0..210 {
+        let expected = (1..20).rev().map(|key| key * 10).find(|&key| key <= target);
+        let found = map.upper_bound_with(|key, _| target.cmp(key)).map(|&(key, _)| key);
+        assert_eq!(found, expected);
+        let expected = (1..20).map(|key| key * 10).find(|&key| key >= target);
+        let found = map.lower_bound_with(|key, _| target.cmp(key)).map(|&(key, _)| key);
+        assert_eq!(found, expected);
+    }
+    assert_eq!(SplayMap::<u32, u32>::new().upper_bound_with(|_, _| cmp::Ordering::Greater), None);
+}
+
+#[test]
+fn check_into_iterator() {
+    let mut map: SplayMap<u32, u32> = (0..5).map(|key| (key, key)).collect();
+    for (_, value) in &mut map {
+        *value *= 3;
+    }
+    let mut sum = 0;
+    for (&key, &value) in &map {
+        assert_eq!(value, key * 3);
+        sum += value;
+    }
+    assert_eq!(sum, 30);
+    let mut keys = Vec::new();
+    for (key, _) in map {
+        keys.push(key);
+    }
+    assert_eq!(keys, vec![0, 1, 2, 3, 4]);
+}
+
+quickcheck! {
+    fn check_order_statistics(operations: Vec<(bool, u8)>) -> bool {
+        let (mut map, mut keys) = (SplayMap::new(), Vec::new());
+        for (insert, key) in operations {
+            let position = keys.binary_search(&key);
+            if insert {
+                map.insert(key, ());
+                if let Err(index) = position {
+                    keys.insert(index, key)
+                }
+            } else {
+                map.remove(&key);
+                if let Ok(index) = position {
+                    keys.remove(index);
+                }
+            }
+            map.check_invariants();
+        }
+        (0..keys.len() + 1).all(|index| {
+            map.nth(index).map(|(&key, _)| key) == keys.get(index).cloned()
+        }) && (0..=255).all(|key| {
+            map.rank(&key) == keys.iter().filter(|&&other| other < key).count()
+        })
+    }
+}
+
+#[test]
+fn check_peek() {
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key, key * 10)).collect();
+    let height = map.height();
+    let values: Vec<_> = (0..12).map(|key| map.peek(&key)).collect();
+    assert_eq!(values[9], Some(&90));
+    assert_eq!(values[0], Some(&0));
+    assert_eq!(values[11], None);
+    assert_eq!(map.height(), height);
+    map.check_invariants();
+}
+
+quickcheck! {
+    fn check_double_ended_iter(keys: Vec<u8>, directions: Vec<bool>) -> bool {
+        let map: SplayMap<u8, ()> = keys.into_iter().map(|key| (key, ())).collect();
+        let (mut front, mut back) = (Vec::new(), Vec::new());
+        let mut iter = map.iter();
+        for forward in directions {
+            if forward {
+                front.extend(iter.next().map(|(&key, _)| key));
+            } else {
+                back.extend(iter.next_back().map(|(&key, _)| key));
+            }
+        }
+        front.extend(iter.by_ref().map(|(&key, _)| key));
+        front.extend(back.into_iter().rev());
+        let mut reversed: Vec<u8> = map.keys().rev().cloned().collect();
+        reversed.reverse();
+        front == reversed && front.windows(2).all(|pair| pair[0] < pair[1]) &&
+            front.len() == map.len() && iter.next_back().is_none()
+    }
+}
+
+#[test]
+fn check_range_rev() {
+    let map: SplayMap<u32, u32> = (0..20).map(|key| (key * 5, key)).collect();
+    let keys: Vec<u32> = map.range(12..=40).rev().map(|(&key, _)| key).collect();
+    assert_eq!(keys, vec![40, 35, 30, 25, 20, 15]);
+    let mut range = map.range(12..40);
+    assert_eq!(range.next_back(), Some((&35, &7)));
+    assert_eq!(range.next(), Some((&15, &3)));
+    assert_eq!(range.len(), 3);
+    assert_eq!(map.iter_from(&90).rev().map(|(&key, _)| key).collect::<Vec<_>>(), vec![95, 90]);
+    assert_eq!(map.values().next_back(), Some(&19));
+    assert_eq!(map.range(50..50).next_back(), None);
+}
+
+
+#[test]
+fn check_clone_degenerate() {
+    let mut map = SplayMap::from_sorted_iter((0..1_000_000u32).map(|key| (key, key)));
+    // Looking the keys up in order leaves a tree that's mostly a single path.
+    for key in 0..1_000_000 {
+        map.get(&key);
+    }
+    assert!(map.height() > 100_000);
+    let copy = map.clone();
+    assert_eq!(copy.height(), map.height());
+    assert_eq!(copy.len(), 1_000_000);
+    assert!(copy.iter().map(|(&key, _)| key).eq(0..1_000_000));
+}
+
+#[test]
+fn check_bounds_on_degenerate_tree() {
+    let mut map = SplayMap::from_sorted_iter((0..1_000_000u32).map(|key| (key * 2, key)));
+    for key in 0..1_000_000 {
+        map.get(&(key * 2));
+    }
+    assert!(map.height() > 100_000);
+    assert_eq!(map.lower_bound_with(|key, _| 1.cmp(key)), Some(&(2, 1)));
+    assert_eq!(map.upper_bound_with(|key, _| 1.cmp(key)), Some(&(0, 0)));
+    assert_eq!(map.lower_bound_with(|key, _| 0.cmp(key)), Some(&(0, 0)));
+    assert_eq!(map.lower_bound_with(|key, _| 2_000_000.cmp(key)), None);
 }
 
+#[test]
+fn check_from_sorted_iter() {
+    let mut map = SplayMap::from_sorted_iter((0..1023).map(|key| (key, key * 2)));
+    map.check_invariants();
+    assert_eq!((map.len(), map.height()), (1023, 10));
+    assert!(map.iter().map(|(&key, &value)| (key, value)).eq((0..1023).map(|key| (key, key * 2))));
+    assert!(SplayMap::<u32, u32>::from_sorted_iter(vec![]).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Keys aren't strictly increasing")]
+fn check_from_sorted_iter_duplicate_keys() {
+    SplayMap::from_sorted_iter(vec![(1, ()), (2, ()), (2, ()), (3, ())]);
+}
+
+#[test]
+fn check_into_keys_and_values() {
+    let map: SplayMap<u32, u32> = (0..6).rev().map(|key| (key, key * 10)).collect();
+    let mut keys = map.clone().into_keys();
+    assert_eq!(keys.len(), 6);
+    assert_eq!((keys.next(), keys.next_back()), (Some(0), Some(5)));
+    assert_eq!(keys.collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    let values: Vec<u32> = map.into_values().rev().collect();
+    assert_eq!(values, vec![50, 40, 30, 20, 10, 0]);
+}
+
+#[test]
+fn check_rebalance() {
+    let mut map = SplayMap::from_sorted_iter((0..1_000_000u32).map(|key| (key, key)));
+    for key in 0..1_000_000 {
+        map.get(&key);
+    }
+    assert!(map.is_degenerate());
+    map.rebalance();
+    assert_eq!((map.len(), map.height()), (1_000_000, 20));
+    assert!(!map.is_degenerate());
+    assert!(map.iter().map(|(&key, &value)| (key, value)).eq((0..1_000_000).map(|key| (key, key))));
+
+    let mut map = SplayMap::<u32, u32>::new();
+    map.rebalance();
+    assert!(map.is_empty());
+}
+
+#[test]
+fn check_diagnostics() {
+    let mut map = SplayMap::from_sorted_iter((0..7u32).map(|key| (key, ())));
+    assert_eq!(map.diagnostics(),
+               TreeStats { len: 7, max_depth: 3, min_depth: 3, average_depth: 17.0 / 7.0 });
+    map.get(&0);
+    let stats = map.diagnostics();
+    assert_eq!((stats.len, stats.max_depth, stats.min_depth), (7, 5, 4));
+
+    let mut map = SplayMap::from_sorted_iter((0..1000u32).map(|key| (key, ())));
+    for key in 0..1000 {
+        map.get(&key);
+    }
+    let stats = map.diagnostics();
+    assert_eq!(stats.max_depth, map.height());
+    assert!(stats.average_depth > 100.0);
+    assert_eq!(SplayMap::<u32, ()>::new().diagnostics(), TreeStats::default());
+}
+
+#[test]
+fn check_get_many_mut() {
+    let mut map: SplayMap<u32, u32> = (0..10).map(|key| (key, key * 10)).collect();
+    let height = map.height();
+    {
+        let [first, second, third] = map.get_many_mut([&7, &2, &9]).unwrap();
+        mem::swap(first, second);
+        *third += 1;
+    }
+    assert_eq!((map[&7], map[&2], map[&9]), (20, 70, 91));
+    assert_eq!(map.height(), height);
+    assert!(map.get_many_mut([&1, &5, &1]).is_none());
+    assert!(map.get_many_mut([&1, &10]).is_none());
+    assert_eq!(map.get_many_mut::<u32, 0>([]), Some([]));
+    assert!(SplayMap::<u32, u32>::new().get_many_mut([&1]).is_none());
+    map.check_invariants();
+}
+
+#[test]
+fn check_get_many_mut_along_one_path() {
+    // The root is 3, with 1 and 5 below it and the even keys as leaves.
+    let mut map = SplayMap::from_sorted_iter((0..7u32).map(|key| (key, key)));
+    {
+        let [leaf, root, middle] = map.get_many_mut([&2, &3, &1]).unwrap();
+        *leaf += 10;
+        *root += 20;
+        *middle += 30;
+        mem::swap(leaf, root);
+    }
+    assert_eq!(map.to_sorted_vec(),
+               vec![(0, 0), (1, 31), (2, 23), (3, 12), (4, 4), (5, 5), (6, 6)]);
+    assert!(map.get_many_mut([&6, &3, &7]).is_none());
+    map.check_invariants();
+}
+
+#[test]
+fn check_send_and_sync() {
+    fn assert_send<T: Send>() {}
+    #[cfg(not(feature = "stats"))]
+    fn assert_sync<T: Sync>() {}
+
+    assert_send::<SplayMap<u32, Vec<u32>>>();
+    #[cfg(not(feature = "stats"))]
+    assert_sync::<SplayMap<u32, Vec<u32>>>();
+}
+
+#[test]
+fn check_from_vec() {
+    let mut map = SplayMap::from(vec![(3, "c"), (1, "a"), (3, "d"), (2, "b"), (1, "e")]);
+    map.check_invariants();
+    assert_eq!(map.height(), 2);
+    assert_eq!(map.into_sorted_vec(), vec![(1, "e"), (2, "b"), (3, "d")]);
+    assert!(SplayMap::<u32, u32>::from(vec![]).is_empty());
+}
+
+quickcheck! {
+    fn check_sorted_vec_round_trip(pairs: Vec<(u32, u32)>) -> bool {
+        let map: SplayMap<u32, u32> = pairs.into_iter().collect();
+        let sorted = map.to_sorted_vec();
+        sorted.windows(2).all(|pair| pair[0].0 < pair[1].0) &&
+            SplayMap::from_sorted_iter(sorted.clone()).into_sorted_vec() == sorted &&
+            map.into_sorted_vec() == sorted
+    }
+}
+
+quickcheck! {
+    fn check_splay_on_read(keys: Vec<u8>, operations: Vec<(u8, u8)>) -> bool {
+        let mut splaying: SplayMap<u8, u32> = keys.into_iter().map(|key| (key, 0)).collect();
+        let mut plain = splaying.clone();
+        plain.set_splay_on_read(false);
+        for (operation, key) in operations {
+            match operation % 4 {
+                0 => assert_eq!(splaying.insert(key, 1), plain.insert(key, 1)),
+                1 => assert_eq!(splaying.remove(&key), plain.remove(&key)),
+                2 => assert_eq!(splaying.contains_key(&key), plain.contains_key(&key)),
+                _ => {
+                    let height = plain.height();
+                    if let Some(value) = plain.get_mut(&key) {
+                        *value += 1;
+                    }
+                    assert_eq!(plain.height(), height);
+                    if let Some(value) = splaying.get_mut(&key) {
+                        *value += 1;
+                    }
+                    assert_eq!(splaying.get(&key), plain.get(&key));
+                }
+            }
+            plain.check_invariants();
+        }
+        splaying == plain
+    }
+}
+
+#[test]
+fn check_predecessor_and_successor() {
+    let mut map: SplayMap<u32, u32> = (1..6).map(|key| (key * 10, key)).collect();
+    map.get(&30);
+    let height = map.height();
+    assert_eq!(map.predecessor(&30), Some((&20, &2)));
+    assert_eq!(map.successor(&30), Some((&40, &4)));
+    assert_eq!(map.predecessor(&35), Some((&30, &3)));
+    assert_eq!(map.successor(&35), Some((&40, &4)));
+    assert_eq!(map.predecessor(&10), None);
+    assert_eq!(map.successor(&50), None);
+    assert_eq!(map.predecessor(&1000), Some((&50, &5)));
+    assert_eq!(map.successor(&0), Some((&10, &1)));
+    assert_eq!(map.height(), height);
+    assert_eq!(SplayMap::<u32, u32>::new().successor(&0), None);
+}
+
+#[test]
+fn check_floor_and_ceiling() {
+    let empty = SplayMap::<u32, u32>::new();
+    assert_eq!((empty.floor(&5), empty.ceiling(&5)), (None, None));
+
+    let single: SplayMap<u32, u32> = vec![(5, 50)].into_iter().collect();
+    assert_eq!((single.floor(&5), single.ceiling(&5)), (Some((&5, &50)), Some((&5, &50))));
+    assert_eq!((single.floor(&4), single.ceiling(&4)), (None, Some((&5, &50))));
+    assert_eq!((single.floor(&6), single.ceiling(&6)), (Some((&5, &50)), None));
+
+    let map: SplayMap<u32, u32> = (1..6).map(|key| (key * 10, key)).collect();
+    assert_eq!((map.floor(&30), map.ceiling(&30)), (Some((&30, &3)), Some((&30, &3))));
+    assert_eq!((map.floor(&35), map.ceiling(&35)), (Some((&30, &3)), Some((&40, &4))));
+}
+
+#[test]
+fn check_splay_on_read_survives_bulk_operations() {
+    // Looks up the smallest key, and returns whether that changed the shape of the tree.
+    fn get_splays(map: &mut SplayMap<u32, u32>) -> bool {
+        let (depth, key) = (map.diagnostics().average_depth, *map.first_key_value().unwrap().0);
+        map.get(&key);
+        map.diagnostics().average_depth != depth
+    }
+
+    let mut map = SplayMap::new();
+    map.set_splay_on_read(false);
+    map.bulk_insert((0..100).map(|key| (key, key)).collect());
+    assert!(!get_splays(&mut map));
+
+    let (mut empty, mut other) = (SplayMap::new(), SplayMap::new());
+    empty.set_splay_on_read(false);
+    other.set_splay_on_read(false);
+    other.bulk_insert((0..100).map(|key| (key, key)).collect());
+    empty.append(&mut other);
+    assert!(!get_splays(&mut empty));
+    other.bulk_insert((200..300).map(|key| (key, key)).collect());
+    assert!(!get_splays(&mut other));
+
+    // Appending pairs that interleave with the existing ones drains `other`.
+    let mut interleaved: SplayMap<u32, u32> = (0..50).map(|key| (key * 2 + 1, key)).collect();
+    interleaved.set_splay_on_read(false);
+    map.append(&mut interleaved);
+    assert!(!get_splays(&mut map));
+    interleaved.bulk_insert((0..100).map(|key| (key, key)).collect());
+    assert!(!get_splays(&mut interleaved));
+
+    // A map that splays on reads keeps doing so after taking the tree of one that doesn't.
+    let mut splaying = SplayMap::new();
+    splaying.append(&mut map);
+    assert!(get_splays(&mut splaying));
+    assert_eq!(splaying.len(), 100);
+}